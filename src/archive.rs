@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use tar::Archive;
+use zip::ZipArchive;
+use crate::config::Config;
+use crate::matcher::Matcher;
+use crate::printer::Printer;
+use crate::search_source;
+
+// Archives searched member by member with -z. Jar files and Office documents are zip
+// archives too.
+const ZIP_EXTENSIONS: [&str; 5] = [".zip", ".jar", ".docx", ".xlsx", ".pptx"];
+
+// Tar archives searched member by member with -z, where the last two are gzipped
+const TAR_EXTENSIONS: [&str; 3] = [".tar", ".tar.gz", ".tgz"];
+
+// Files decompressed while they are searched with -z
+const GZIP_EXTENSIONS: [&str; 2] = [".gz", ".tgz"];
+
+// Separates the archive's path from a member's path, as in `archive.zip!path/inside.txt`
+const MEMBER_SEPARATOR: char = '!';
+
+pub fn is_zip_archive(file: &str) -> bool {
+    has_extension(file, &ZIP_EXTENSIONS)
+}
+
+pub fn is_tar_archive(file: &str) -> bool {
+    has_extension(file, &TAR_EXTENSIONS)
+}
+
+pub fn is_gzipped(file: &str) -> bool {
+    has_extension(file, &GZIP_EXTENSIONS)
+}
+
+fn has_extension(file: &str, extensions: &[&str]) -> bool {
+    let file = file.to_ascii_lowercase();
+    extensions.iter().any(|extension| file.ends_with(extension))
+}
+
+// Search each file in a zip archive as if it were a file of its own
+pub(crate) fn search_zip<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    printer: &mut Printer<W>,
+    file: &str,
+    handle: File,
+) -> io::Result<bool> {
+    let mut archive = ZipArchive::new(BufReader::new(handle)).map_err(io::Error::other)?;
+    let mut any_matched = false;
+
+    for index in 0..archive.len() {
+        let member = archive.by_index(index).map_err(io::Error::other)?;
+        if !member.is_file() {
+            continue;
+        }
+        let name = format!("{}{}{}", file, MEMBER_SEPARATOR, member.name());
+
+        printer.begin_file();
+        let matched = search_source(config, matcher, printer, &name, BufReader::new(member))?;
+        any_matched |= matched;
+
+        // With -q the first match settles the result, so skip the remaining members
+        if matched && config.quiet {
+            break;
+        }
+    }
+
+    Ok(any_matched)
+}
+
+// Search each file in a tar archive as if it were a file of its own. The archive is read
+// as a stream, so members are searched in the order they were archived.
+pub(crate) fn search_tar<R: Read, W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    printer: &mut Printer<W>,
+    file: &str,
+    reader: R,
+) -> io::Result<bool> {
+    let mut archive = Archive::new(reader);
+    let mut any_matched = false;
+
+    for member in archive.entries()? {
+        let member = member?;
+        if !member.header().entry_type().is_file() {
+            continue;
+        }
+        let name = format!("{}{}{}", file, MEMBER_SEPARATOR, member.path()?.display());
+
+        printer.begin_file();
+        let matched = search_source(config, matcher, printer, &name, BufReader::new(member))?;
+        any_matched |= matched;
+
+        if matched && config.quiet {
+            break;
+        }
+    }
+
+    Ok(any_matched)
+}
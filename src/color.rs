@@ -0,0 +1,243 @@
+// Colors and styles for the parts of the output, set with --colors
+use colored::{Color, Colorize};
+
+// How one part of the output is painted. The default style leaves the text as it is.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    pub fn fg(color: Color) -> Style {
+        Style { fg: Some(color), ..Style::default() }
+    }
+
+    // Build a style from the SGR parameters of a terminal escape sequence, such as `01;31`
+    // for bold red, as GREP_COLORS gives them. 256-color codes are shown in true color.
+    // Returns None for parameters that aren't understood.
+    pub fn from_sgr(sgr: &str) -> Option<Style> {
+        let mut style = Style::default();
+        if sgr.is_empty() {
+            return Some(style);
+        }
+        let mut codes = sgr.split(';').map(|code| code.parse::<u8>().ok());
+        while let Some(code) = codes.next() {
+            match code? {
+                0 => style = Style::default(),
+                1 => style.bold = true,
+                2 => style.dimmed = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                22 => (style.bold, style.dimmed) = (false, false),
+                23 => style.italic = false,
+                24 => style.underline = false,
+                code @ 30..=37 => style.fg = Some(ansi_color(code - 30)),
+                38 => style.fg = Some(extended_color(&mut codes)?),
+                39 => style.fg = None,
+                code @ 40..=47 => style.bg = Some(ansi_color(code - 40)),
+                48 => style.bg = Some(extended_color(&mut codes)?),
+                49 => style.bg = None,
+                code @ 90..=97 => style.fg = Some(ansi_color(code - 90 + 8)),
+                code @ 100..=107 => style.bg = Some(ansi_color(code - 100 + 8)),
+                _ => return None,
+            }
+        }
+        Some(style)
+    }
+
+    pub fn paint(&self, text: &str) -> String {
+        if *self == Style::default() {
+            return text.to_string();
+        }
+        let mut painted = text.normal();
+        if let Some(fg) = self.fg {
+            painted = painted.color(fg);
+        }
+        if let Some(bg) = self.bg {
+            painted = painted.on_color(bg);
+        }
+        if self.bold {
+            painted = painted.bold();
+        }
+        if self.dimmed {
+            painted = painted.dimmed();
+        }
+        if self.italic {
+            painted = painted.italic();
+        }
+        if self.underline {
+            painted = painted.underline();
+        }
+        painted.to_string()
+    }
+}
+
+// The style of each part of the output that can be colored
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorSpecs {
+    pub matched: Style,
+    pub path: Style,
+    pub line: Style,
+    pub column: Style,
+    pub separator: Style,
+}
+
+impl Default for ColorSpecs {
+    fn default() -> ColorSpecs {
+        ColorSpecs {
+            matched: Style::fg(Color::Red),
+            path: Style::default(),
+            line: Style::default(),
+            column: Style::default(),
+            separator: Style::default(),
+        }
+    }
+}
+
+impl ColorSpecs {
+    // The built-in palettes selected with --theme
+    pub fn theme(name: &str) -> Option<ColorSpecs> {
+        let bold = |color| Style { bold: true, ..Style::fg(color) };
+        let rgb = |r, g, b| Color::TrueColor { r, g, b };
+        match name {
+            "dark" => Some(ColorSpecs {
+                matched: bold(Color::BrightRed),
+                path: Style::fg(Color::BrightMagenta),
+                line: Style::fg(Color::BrightGreen),
+                column: Style::fg(Color::BrightGreen),
+                separator: Style::fg(Color::BrightCyan),
+            }),
+            "light" => Some(ColorSpecs {
+                matched: bold(Color::Red),
+                path: Style::fg(Color::Magenta),
+                line: Style::fg(Color::Green),
+                column: Style::fg(Color::Green),
+                separator: Style::fg(Color::Blue),
+            }),
+            "solarized" => Some(ColorSpecs {
+                matched: bold(rgb(0xdc, 0x32, 0x2f)),
+                path: Style::fg(rgb(0x6c, 0x71, 0xc4)),
+                line: Style::fg(rgb(0x85, 0x99, 0x00)),
+                column: Style::fg(rgb(0x85, 0x99, 0x00)),
+                separator: Style::fg(rgb(0x2a, 0xa1, 0x98)),
+            }),
+            _ => None,
+        }
+    }
+
+    // Apply a spec of the form `part:fg:color`, `part:bg:color`, `part:style:name` or
+    // `part:none`, where the part is match, path, line, column or separator. Colors are
+    // names such as `yellow` or `bright blue`, or `r,g,b` values. Returns None for a
+    // spec that can't be parsed.
+    pub fn apply(&mut self, spec: &str) -> Option<()> {
+        let mut fields = spec.split(':');
+        let part = match fields.next()? {
+            "match" => &mut self.matched,
+            "path" => &mut self.path,
+            "line" => &mut self.line,
+            "column" => &mut self.column,
+            "separator" => &mut self.separator,
+            _ => return None,
+        };
+        match (fields.next()?, fields.next(), fields.next()) {
+            ("none", None, _) => *part = Style::default(),
+            ("fg", Some(color), None) => part.fg = Some(parse_color(color)?),
+            ("bg", Some(color), None) => part.bg = Some(parse_color(color)?),
+            ("style", Some(name), None) => match name {
+                "bold" => part.bold = true,
+                "nobold" => part.bold = false,
+                "dimmed" => part.dimmed = true,
+                "nodimmed" => part.dimmed = false,
+                "italic" => part.italic = true,
+                "noitalic" => part.italic = false,
+                "underline" => part.underline = true,
+                "nounderline" => part.underline = false,
+                _ => return None,
+            },
+            _ => return None,
+        }
+        Some(())
+    }
+
+    // Apply the GNU grep GREP_COLORS variable, a list like `ms=01;31:fn=35:ln=32:se=36`.
+    // The match (ms, or mt for all matches), file name, line number and separator colors
+    // are used. Other capabilities and entries that can't be parsed are ignored, as GNU
+    // grep does.
+    pub fn apply_grep_colors(&mut self, value: &str) {
+        for entry in value.split(':') {
+            let Some((name, sgr)) = entry.split_once('=') else {
+                continue;
+            };
+            let part = match name {
+                "ms" | "mt" => &mut self.matched,
+                "fn" => &mut self.path,
+                "ln" => &mut self.line,
+                "se" => &mut self.separator,
+                _ => continue,
+            };
+            if let Some(style) = Style::from_sgr(sgr) {
+                *part = style;
+            }
+        }
+    }
+}
+
+// The 16 basic terminal colors, by their number in the 256-color palette
+fn ansi_color(index: u8) -> Color {
+    const COLORS: [Color; 16] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::White,
+        Color::BrightBlack,
+        Color::BrightRed,
+        Color::BrightGreen,
+        Color::BrightYellow,
+        Color::BrightBlue,
+        Color::BrightMagenta,
+        Color::BrightCyan,
+        Color::BrightWhite,
+    ];
+    COLORS[index as usize]
+}
+
+// Read the rest of a `38;5;n` or `38;2;r;g;b` color from the SGR codes
+fn extended_color(codes: &mut impl Iterator<Item = Option<u8>>) -> Option<Color> {
+    match codes.next()?? {
+        5 => {
+            let index = codes.next()??;
+            Some(match index {
+                0..=15 => ansi_color(index),
+                // A 6x6x6 color cube followed by 24 shades of gray
+                16..=231 => {
+                    let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+                    let n = index - 16;
+                    Color::TrueColor { r: level(n / 36), g: level(n / 6 % 6), b: level(n % 6) }
+                }
+                _ => {
+                    let gray = 8 + (index - 232) * 10;
+                    Color::TrueColor { r: gray, g: gray, b: gray }
+                }
+            })
+        }
+        2 => Some(Color::TrueColor { r: codes.next()??, g: codes.next()??, b: codes.next()?? }),
+        _ => None,
+    }
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    let rgb: Vec<&str> = name.split(',').collect();
+    if let [r, g, b] = rgb[..] {
+        return Some(Color::TrueColor { r: r.trim().parse().ok()?, g: g.trim().parse().ok()?, b: b.trim().parse().ok()? });
+    }
+    name.parse().ok()
+}
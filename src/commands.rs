@@ -0,0 +1,33 @@
+// The subcommands, picked by the first argument. Searching is the default, so
+// `grep <pattern>` is the same as `grep search <pattern>`.
+use std::ffi::OsStr;
+use crate::completions::COMPLETIONS_COMMAND;
+use crate::replace::REPLACE_COMMAND;
+
+pub const SEARCH_COMMAND: &str = "search";
+pub const TYPES_COMMAND: &str = "types";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    Search,
+    // Lists the file types known to --type, the same as `grep --type-list`
+    Types,
+    Replace,
+    Completions,
+}
+
+impl Command {
+    // Pick the subcommand for the arguments, returning them without the program name
+    // when a subcommand is named, so that its name comes first instead. A first argument
+    // naming a subcommand is always taken as one, so such a pattern needs -e or `--`.
+    pub fn from_args<T: AsRef<OsStr>>(args: &[T]) -> (Command, &[T]) {
+        let command = match args.get(1).and_then(|arg| arg.as_ref().to_str()) {
+            Some(SEARCH_COMMAND) => Command::Search,
+            Some(TYPES_COMMAND) => Command::Types,
+            Some(REPLACE_COMMAND) => Command::Replace,
+            Some(COMPLETIONS_COMMAND) => Command::Completions,
+            _ => return (Command::Search, args),
+        };
+        (command, &args[1..])
+    }
+}
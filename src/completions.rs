@@ -0,0 +1,60 @@
+// The `completions` subcommand, which prints a shell completion script
+use std::error::Error;
+use std::ffi::OsString;
+use std::io;
+use clap::builder::PossibleValuesParser;
+use clap::{CommandFactory, Parser};
+use clap_complete::{generate, Shell};
+use crate::commands::{SEARCH_COMMAND, TYPES_COMMAND};
+use crate::replace::REPLACE_COMMAND;
+use crate::{config, replace};
+use crate::types::Types;
+
+pub const COMPLETIONS_COMMAND: &str = "completions";
+
+pub struct CompletionsConfig {
+    pub shell: Shell,
+}
+
+// The arguments following the `completions` subcommand, as clap sees them
+#[derive(Parser)]
+#[command(
+    name = "grep completions",
+    bin_name = "grep completions",
+    about = "Prints a completion script for <SHELL>, e.g. `grep completions bash > /etc/bash_completion.d/grep`"
+)]
+struct CompletionsArgs {
+    #[arg(help = "The shell to complete in")]
+    shell: Shell,
+}
+
+impl CompletionsConfig {
+    // Parse the arguments following the `completions` subcommand, starting with its name
+    pub fn new<I, T>(args: I) -> Result<CompletionsConfig, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = CompletionsArgs::try_parse_from(args)?;
+        Ok(CompletionsConfig { shell: args.shell })
+    }
+}
+
+// Print the completion script for a search and the subcommands. The built-in file types
+// are offered for --type, though the option accepts any name.
+pub fn run_completions(config: CompletionsConfig) -> Result<bool, Box<dyn Error>> {
+    let types: Vec<String> = Types::builtin().iter().map(|(name, _)| name.to_string()).collect();
+    let search = config::command().mut_arg("types", |arg| arg.value_parser(PossibleValuesParser::new(types)));
+    let mut command = search
+        .clone()
+        .subcommand(search.name(SEARCH_COMMAND).about("Search files, the same as leaving out the subcommand"))
+        .subcommand(
+            clap::Command::new(TYPES_COMMAND)
+                .about("List the file types known to --type, the same as --type-list")
+                .arg(clap::Arg::new("type-add").long("type-add").value_name("NAME:GLOB,...")),
+        )
+        .subcommand(replace::command().name(REPLACE_COMMAND))
+        .subcommand(CompletionsArgs::command().name(COMPLETIONS_COMMAND));
+    generate(config.shell, &mut command, "grep", &mut io::stdout());
+    Ok(true)
+}
@@ -0,0 +1,695 @@
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
+use std::thread;
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{Arg, ArgAction, Command, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use encoding_rs::Encoding;
+use glob::Pattern;
+use crate::color::ColorSpecs;
+use crate::defaults::{NO_CONFIG_ID, PROFILE_ID};
+use crate::files::{is_stdin, path_from_bytes};
+use crate::hyperlink::DEFAULT_HYPERLINK_FORMAT;
+use crate::matcher::{Engine, MatchOptions};
+use crate::pager::pager_from_env;
+use crate::printer::GROUP_SEPARATOR;
+use crate::template::Template;
+use crate::types::Types;
+
+const USAGE: &str = "grep [search] [OPTIONS] <pattern> [files...]
+       grep [search] [OPTIONS] -e <pattern>... [files...]
+       grep [search] [OPTIONS] --file=<patterns file> [files...]
+       grep [search] --files [OPTIONS] [files...]
+       grep types [--type-add <definition>...]
+       grep replace [OPTIONS] <pattern> <replacement> <files...>
+       grep completions <shell>";
+
+const AFTER_HELP: &str = "Searching is the default, so the search subcommand can be left out. See
+`grep replace --help` and `grep completions --help` for the other subcommands.
+A pattern named like a subcommand (search, types, replace or completions) has to be given
+with -e or after '--', as in `grep -e search notes.txt`
+<pattern> is a regular expression, e.g. '^fn \\w+\\('
+Reads standard input when no files are given or a file is '-'
+Lines can end with '\\r\\n' as well as '\\n', and the '\\r' is neither matched nor printed
+Arguments after '--' are never options, so `grep -- -r file` searches for '-r'
+Options in the GREP_RUST_OPTS environment variable, quoted as in a shell, come before the
+command line's, after those of the configuration files
+Any flag can be turned off again with --no-<flag>, e.g. --no-smart-case, or --pager for
+--no-pager, to undo one set in a configuration file";
+
+// When to color the output, as given with --color
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+// The command line as clap sees it, before it is turned into a Config
+#[derive(Parser)]
+#[command(name = "grep", override_usage = USAGE, after_help = AFTER_HELP, args_override_self = true)]
+struct Args {
+    // Option values may start with a dash, as in `-e -r` or `--group-separator --`
+    #[arg(short = 'e', long, value_name = "PATTERN", allow_hyphen_values = true, help = "Search for <PATTERN>; repeat to match any of several patterns")]
+    regexp: Vec<String>,
+    #[arg(long, value_name = "FILE", help = "Read patterns from <FILE>, one per line (blank lines are ignored)")]
+    file: Vec<String>,
+    #[arg(short = 'F', long, help = "Treat patterns as fixed strings rather than regular expressions")]
+    fixed_strings: bool,
+    #[arg(long, value_name = "NAME", default_value = "default", value_parser = parse_engine,
+        help = "Regex engine: 'default', or 'fancy' for lookaround and backreferences")]
+    engine: Engine,
+    #[arg(short, long, help = "Case-insensitive search. Only simple case folding is done, so ß doesn't match SS and İ doesn't match i")]
+    ignore_case: bool,
+    #[arg(short = 'S', long, help = "Case-insensitive search unless the pattern contains uppercase")]
+    smart_case: bool,
+    #[arg(short = 'n', long, help = "Print line numbers")]
+    line_number: bool,
+    #[arg(short = 'v', long, help = "Invert match (exclude lines that match the pattern)")]
+    invert_match: bool,
+    #[arg(short = 'x', long, help = "Match only whole lines")]
+    line_regexp: bool,
+    #[arg(short = 'U', long, conflicts_with_all = ["invert_match", "after_context", "before_context", "context", "passthru"],
+        help = "Match the whole file at once so patterns can span lines (can't be combined with -v, context or --passthru)")]
+    multiline: bool,
+
+    #[arg(short, long, help = "Recursive directory search")]
+    recursive: bool,
+    #[arg(long, value_name = "NUM",
+        help = "Only search files at most <NUM> levels into directories given to -r, where the directory's own files are at level 1")]
+    max_depth: Option<usize>,
+    #[arg(long, value_name = "NUM", default_value_t = 0, help = "Only search files at least <NUM> levels into directories given to -r")]
+    min_depth: usize,
+    #[arg(long, help = "Descend into symlinked directories with -r, walking each directory once")]
+    follow: bool,
+    #[arg(long, help = "Don't descend into directories on other file systems, such as /proc, with -r")]
+    one_file_system: bool,
+    #[arg(long, help = "Search hidden files and directories, such as .cache, with -r")]
+    hidden: bool,
+    #[arg(short = 'u', long,
+        help = "Search everything -r skips by default: files excluded by .gitignore, .ignore and .grepignore files, hidden files, and .git, .hg, .svn and node_modules")]
+    no_ignore: bool,
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob,
+        help = "Only search files whose names match <GLOB>, e.g. '*.rs', with -r (repeatable)")]
+    include: Vec<Pattern>,
+    #[arg(short = 't', long = "type", value_name = "NAME", help = "Only search files of type <NAME>, e.g. 'rust' or 'py', with -r (repeatable)")]
+    types: Vec<String>,
+    #[arg(long, value_name = "NAME:GLOB,...", help = "Define file type <NAME> for --type, or add globs to an existing type")]
+    type_add: Vec<String>,
+    #[arg(long, value_name = "FILE", help = "Also search the files listed in <FILE>, one per line, or on standard input for '-'")]
+    files_from: Vec<PathBuf>,
+    #[arg(short = '0', long, help = "Separate the files listed with --files-from by NUL bytes, as from `find -print0`")]
+    null_data_files: bool,
+    #[arg(long, help = "Print the files that would be searched, without searching them")]
+    files: bool,
+    #[arg(long, help = "List the file types known to --type, with their globs")]
+    type_list: bool,
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob,
+        help = "Skip files whose names match <GLOB>, e.g. '*.min.js', with -r or in a wildcard file list (repeatable)")]
+    exclude: Vec<Pattern>,
+    #[arg(long, value_name = "FILE", help = "Read --exclude globs from <FILE>, one per line (blank lines are ignored)")]
+    exclude_from: Vec<String>,
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob,
+        help = "Don't descend into directories whose names match <GLOB> with -r (repeatable)")]
+    exclude_dir: Vec<Pattern>,
+
+    #[arg(short = 'f', long, help = "Print filenames")]
+    with_filename: bool,
+    #[arg(long, overrides_with = "no_heading",
+        help = "With -f, print each file's name once above its matches instead of before every line (the default when writing to a terminal)")]
+    heading: bool,
+    #[arg(long, overrides_with = "heading", help = "With -f, print the file name before every line")]
+    no_heading: bool,
+    // Only one output mode can be chosen
+    #[arg(long, conflicts_with_all = ["json", "json_lines", "csv", "format"],
+        help = "Print each match as file:line:column:text, for Vim's quickfix list")]
+    vimgrep: bool,
+    #[arg(long, help = "Print the 1-based column of the first match on each line (or of each match with -o)")]
+    column: bool,
+    #[arg(long, conflicts_with_all = ["json_lines", "csv", "format"],
+        help = "Print the matching lines as a JSON array of objects giving the path, line number, column, byte offset, text and the span of each match")]
+    json: bool,
+    #[arg(long, conflicts_with_all = ["csv", "format"],
+        help = "Print a stream of JSON objects, one per line: `begin` and `end` around each file with matches, `match` and `context` for its lines, and a final `summary`")]
+    json_lines: bool,
+    #[arg(long, conflicts_with = "format", help = "Print the matching lines as CSV rows of path, line, column and text, after a header row")]
+    csv: bool,
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_template,
+        help = "Print each matching line in the layout of <TEMPLATE>, where {path}, {line}, {column}, {offset}, {text} and {match} are filled in, e.g. '{path}\\t{line}\\t{text}'")]
+    format: Option<Template>,
+    #[arg(short = 'b', long, help = "Print the byte offset of each line (or of each match with -o)")]
+    byte_offset: bool,
+    // The value is optional, so it can only be given as `--expand-tabs=<WIDTH>`
+    #[arg(long, value_name = "WIDTH", num_args = 0..=1, require_equals = true, default_missing_value = "8",
+        value_parser = parse_tab_width,
+        help = "Print tabs in lines as spaces up to the next tab stop, every <WIDTH> columns (8 by default)")]
+    expand_tabs: Option<usize>,
+    #[arg(short = 'M', long, value_name = "NUM",
+        help = "Show at most <NUM> characters of a printed line, centered on its first match, with ellipses where it is cut and how many bytes were left out")]
+    max_columns: Option<usize>,
+    #[arg(short = 'm', long, value_name = "NUM", help = "Stop searching a file after <NUM> matching lines")]
+    max_count: Option<usize>,
+    #[arg(short = 'o', long, help = "Print only the matched parts of each line, one per line")]
+    only_matching: bool,
+    #[arg(long, value_name = "TEXT", allow_hyphen_values = true, help = "Print matches rewritten with <TEXT>, where $1 or ${name} refer to capture groups")]
+    replace: Option<String>,
+    #[arg(short = 'c', long, help = "Print only a count of matching lines per file")]
+    count: bool,
+    #[arg(short = 'l', long, help = "Print only the names of files containing a match")]
+    files_with_matches: bool,
+    #[arg(short = 'L', long, help = "Print only the names of files without a match")]
+    files_without_match: bool,
+    #[arg(short = 'Z', long,
+        help = "Follow file names with a NUL byte: in place of `: ` before matching lines and counts, and of the newline after names listed by -l, -L and --files, for `xargs -0`")]
+    null: bool,
+    #[arg(short = 'q', long, alias = "silent", help = "Quiet mode: print nothing and stop at the first match")]
+    quiet: bool,
+
+    // The value is optional, so it can only be given as `--color=<WHEN>`
+    #[arg(long, value_name = "WHEN", default_value = "auto", num_args = 0..=1, require_equals = true,
+        default_missing_value = "always",
+        help = "Color matches 'always', 'never', or when writing to a terminal ('auto'). A bare --color means 'always'. With 'auto', a non-empty NO_COLOR environment variable disables colors and CLICOLOR_FORCE forces them")]
+    color: ColorChoice,
+    // Specs are checked here but applied once the theme is known
+    #[arg(long, value_name = "SPEC", value_parser = parse_color_spec,
+        help = "Set how part of the output is colored, as 'part:fg:color', 'part:bg:color', 'part:style:name' or 'part:none'. Parts are match, path, line, column and separator, colors are names like 'yellow' or 'bright blue' or 'r,g,b', and styles are bold, dimmed, italic and underline (or nobold etc.). Can be repeated, and overrides the ms, fn, ln and se colors of a GNU-style GREP_COLORS variable")]
+    colors: Vec<String>,
+    #[arg(long, value_name = "NAME", value_parser = parse_theme,
+        help = "Start from the 'dark', 'light' or 'solarized' palette, in place of red matches and GREP_COLORS, before applying --colors")]
+    theme: Option<ColorSpecs>,
+    // Like --color, the value can only be given as `--hyperlink=<FORMAT>`
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = DEFAULT_HYPERLINK_FORMAT,
+        help = "When output is colored, make file names OSC 8 links that open the file. The link is file://{path} unless <FORMAT> gives another, where {path}, {line} and {column} are filled in, e.g. 'vscode://file{path}:{line}:{column}'")]
+    hyperlink: Option<String>,
+    #[arg(long, help = "Don't send the results to $PAGER (by default 'less -R') when writing to a terminal")]
+    no_pager: bool,
+    #[arg(long, help = "Flush the output after every line, so results show up at once when piped (the default when writing to a terminal)")]
+    line_buffered: bool,
+
+    #[arg(short = 'A', long, value_name = "NUM", help = "Print <NUM> lines of trailing context after each match")]
+    after_context: Option<usize>,
+    #[arg(short = 'B', long, value_name = "NUM", help = "Print <NUM> lines of leading context before each match")]
+    before_context: Option<usize>,
+    #[arg(short = 'C', long, value_name = "NUM", help = "Print <NUM> lines of context before and after each match")]
+    context: Option<usize>,
+    #[arg(long, value_name = "SEP", allow_hyphen_values = true, overrides_with = "no_group_separator",
+        help = "Print <SEP> between groups of lines that aren't adjacent, instead of '--'")]
+    group_separator: Option<String>,
+    #[arg(long, overrides_with = "group_separator", help = "Print nothing between groups of lines")]
+    no_group_separator: bool,
+    #[arg(long, alias = "passthrough", help = "Print every line, coloring the matches, as context around the matching lines")]
+    passthru: bool,
+
+    #[arg(short = 'j', long, value_name = "NUM", default_value_t = 0, help = "Search <NUM> files at a time (default: one per CPU)")]
+    threads: usize,
+    #[arg(short = 'z', long, help = "Search inside .gz files and .zip and .tar archives (members shown as archive.zip!member)")]
+    search_zip: bool,
+    #[arg(long, value_name = "SIZE", value_parser = parse_size,
+        help = "Skip files larger than <SIZE> bytes, which can end in K, M or G, e.g. '10M'")]
+    max_filesize: Option<u64>,
+    #[arg(long, help = "Search large files through a memory map instead of reading them")]
+    mmap: bool,
+    #[arg(long, value_name = "NAME", default_value = "auto", value_parser = parse_encoding,
+        help = "Decode files as <NAME>, e.g. 'latin1' or 'utf16le', instead of detecting UTF-16 from a byte order mark ('auto')")]
+    encoding: ForcedEncoding,
+    #[arg(short = 'a', long, help = "Search binary files as text, replacing unprintable characters in output")]
+    text: bool,
+    #[arg(long, help = "Print how many files, lines and bytes were searched and how long it took")]
+    stats: bool,
+    #[arg(long, help = "Print the --stats counters as a JSON object on stderr, or with --json-lines as part of the summary event")]
+    stats_json: bool,
+    #[arg(long, help = "Report skipped files, such as binary files, on stderr")]
+    verbose: bool,
+    #[arg(short = 's', long, help = "Don't report files that are missing or can't be read. They still make the exit status 2")]
+    no_messages: bool,
+
+    #[arg(value_name = "PATTERN | FILE", help = "The pattern, unless given with -e or --file, followed by the files to search")]
+    positionals: Vec<OsString>,
+}
+
+// An encoding given with --encoding, or None for 'auto'. An alias keeps clap from
+// treating the option as one that may be left out.
+type ForcedEncoding = Option<&'static Encoding>;
+
+// The default config has every option off, with no patterns or files, and is where
+// SearcherBuilder starts from
+#[derive(Default)]
+pub struct Config {
+    pub type_list: bool,
+    pub list_files: bool,
+    pub patterns: Vec<String>,
+    pub pattern_files: Vec<String>,
+    pub filenames: Vec<PathBuf>,
+    // Files listing more files to search, where `-` is standard input
+    pub files_from: Vec<PathBuf>,
+    // The --files-from lists are separated by NUL bytes rather than newlines
+    pub null_data_files: bool,
+    pub is_case_insensitive: bool,
+    pub smart_case: bool,
+    pub print_line_no: bool,
+    pub invert_match: bool,
+    pub whole_line: bool,
+    pub fixed_strings: bool,
+    pub multiline: bool,
+    pub engine: Engine,
+    pub recursive_search: bool,
+    // Deepest level of a directory searched with -r whose files are searched, if limited
+    pub max_depth: Option<usize>,
+    pub min_depth: usize,
+    pub follow: bool,
+    pub one_file_system: bool,
+    pub hidden: bool,
+    pub no_ignore: bool,
+    // Globs that file names found by -r must match one of, or empty to search every file
+    pub include: Vec<Pattern>,
+    // Names of the file types -r is limited to, or empty to search files of any type
+    pub types: Vec<String>,
+    // File types defined with --type-add, as `name:glob,glob`
+    pub type_defs: Vec<String>,
+    // Globs for file names that -r and wildcard file arguments leave out
+    pub exclude: Vec<Pattern>,
+    pub exclude_files: Vec<String>,
+    // Globs for directory names that -r doesn't descend into
+    pub exclude_dir: Vec<Pattern>,
+    pub search_zip: bool,
+    // Size in bytes above which files are skipped
+    pub max_filesize: Option<u64>,
+    pub mmap: bool,
+    // Number of worker threads, where 0 picks one per CPU
+    pub threads: usize,
+    // Encoding forced with --encoding, or None to detect it from a byte order mark
+    pub encoding: Option<&'static Encoding>,
+    pub text: bool,
+    pub stats: bool,
+    pub stats_json: bool,
+    pub verbose: bool,
+    // Files that can't be read are skipped without a message
+    pub no_messages: bool,
+    pub print_filenames: bool,
+    // File names from -f are printed once as a heading above each file's matches
+    pub heading: bool,
+    pub vimgrep: bool,
+    pub json: bool,
+    pub json_lines: bool,
+    pub csv: bool,
+    pub format: Option<Template>,
+    pub column: bool,
+    pub print_byte_offset: bool,
+    // With --expand-tabs, the distance between tab stops in printed lines
+    pub tab_width: Option<usize>,
+    // Printed lines longer than this many characters are cut down to a window around the
+    // first match
+    pub max_columns: Option<usize>,
+    pub coloured_output: bool,
+    pub colors: ColorSpecs,
+    // The link format, when file names are hyperlinks
+    pub hyperlink: Option<String>,
+    // The command the results are piped through, when they go to a pager
+    pub pager: Option<String>,
+    pub line_buffered: bool,
+    pub only_matching: bool,
+    pub replace: Option<String>,
+    pub max_count: Option<usize>,
+    pub count_only: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    // File names are followed by NUL instead of the `: ` of a prefix or the newline
+    // after names listed by -l, -L and --files
+    pub null: bool,
+    pub quiet: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    // Printed between groups of context that aren't adjacent, unless there is none
+    pub group_separator: Option<String>,
+    // Every line that isn't selected is printed as context
+    pub passthru: bool,
+}
+
+impl Config {
+    // Parse command line argument and create a Config object. File names needn't be UTF-8.
+    pub fn new<I, T>(args: I) -> Result<Config, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = command().try_get_matches_from(args).map_err(|e| suggest_flag(e, &command()))?;
+        let args = Args::from_arg_matches(&matches)?;
+        let mut positionals = args.positionals.into_iter();
+
+        // Without -e or --file the first positional argument is the pattern, except with
+        // --files where every positional argument is a file, and with --type-list which
+        // needs no pattern
+        let mut patterns = args.regexp;
+        if patterns.is_empty() && args.file.is_empty() && !args.files && !args.type_list {
+            let pattern = positionals.next().ok_or_else(|| {
+                command().error(ErrorKind::MissingRequiredArgument, "a pattern is required, either first or with -e or --file")
+            })?;
+            let pattern = pattern
+                .into_string()
+                .map_err(|_| command().error(ErrorKind::InvalidUtf8, "the pattern isn't valid UTF-8"))?;
+            patterns.push(pattern);
+        }
+        // With no files given the search reads from standard input
+        let filenames = positionals.map(PathBuf::from).collect();
+
+        let coloured_output = match args.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => auto_color(),
+        };
+
+        // A theme replaces the default colors along with those of GNU grep's GREP_COLORS,
+        // and --colors adjusts either
+        let mut colors = args.theme.unwrap_or_else(|| {
+            let mut colors = ColorSpecs::default();
+            if let Ok(grep_colors) = env::var("GREP_COLORS") {
+                colors.apply_grep_colors(&grep_colors);
+            }
+            colors
+        });
+        for spec in &args.colors {
+            colors.apply(spec);
+        }
+
+        Ok(Config {
+            type_list: args.type_list,
+            list_files: args.files,
+            patterns,
+            pattern_files: args.file,
+            filenames,
+            files_from: args.files_from,
+            null_data_files: args.null_data_files,
+            is_case_insensitive: args.ignore_case,
+            smart_case: args.smart_case,
+            print_line_no: args.line_number,
+            invert_match: args.invert_match,
+            whole_line: args.line_regexp,
+            fixed_strings: args.fixed_strings,
+            multiline: args.multiline,
+            engine: args.engine,
+            recursive_search: args.recursive,
+            max_depth: args.max_depth,
+            min_depth: args.min_depth,
+            follow: args.follow,
+            one_file_system: args.one_file_system,
+            hidden: args.hidden,
+            no_ignore: args.no_ignore,
+            include: args.include,
+            types: args.types,
+            type_defs: args.type_add,
+            exclude: args.exclude,
+            exclude_files: args.exclude_from,
+            exclude_dir: args.exclude_dir,
+            search_zip: args.search_zip,
+            max_filesize: args.max_filesize,
+            mmap: args.mmap,
+            threads: args.threads,
+            encoding: args.encoding,
+            text: args.text,
+            stats: args.stats,
+            stats_json: args.stats_json,
+            verbose: args.verbose,
+            no_messages: args.no_messages,
+            print_filenames: args.with_filename,
+            heading: if args.heading || args.no_heading { args.heading } else { io::stdout().is_terminal() },
+            vimgrep: args.vimgrep,
+            json: args.json,
+            json_lines: args.json_lines,
+            csv: args.csv,
+            format: args.format,
+            column: args.column,
+            print_byte_offset: args.byte_offset,
+            tab_width: args.expand_tabs,
+            max_columns: args.max_columns,
+            coloured_output,
+            colors,
+            // Escape sequences are only written where colors are, which is taken to mean a
+            // terminal that can show them
+            hyperlink: args.hyperlink.filter(|_| coloured_output),
+            // Nothing is printed with -q, so there is nothing to page
+            pager: pager_from_env().filter(|_| !args.no_pager && !args.quiet && io::stdout().is_terminal()),
+            // Output to a terminal is for someone to read as it comes
+            line_buffered: args.line_buffered || io::stdout().is_terminal(),
+            only_matching: args.only_matching,
+            replace: args.replace,
+            max_count: args.max_count,
+            count_only: args.count,
+            files_with_matches: args.files_with_matches,
+            files_without_match: args.files_without_match,
+            null: args.null,
+            quiet: args.quiet,
+            // -A and -B take precedence over -C
+            before_context: args.before_context.or(args.context).unwrap_or(0),
+            after_context: args.after_context.or(args.context).unwrap_or(0),
+            group_separator: if args.no_group_separator {
+                None
+            } else {
+                Some(args.group_separator.unwrap_or_else(|| GROUP_SEPARATOR.to_string()))
+            },
+            passthru: args.passthru,
+        })
+    }
+
+    // Collect the patterns given on the command line and in pattern files
+    pub fn read_patterns(&self) -> io::Result<Vec<String>> {
+        let mut patterns = self.patterns.clone();
+        for file in &self.pattern_files {
+            let contents = fs::read_to_string(file)?;
+            patterns.extend(contents.lines().filter(|line| !line.is_empty()).map(String::from));
+        }
+        Ok(patterns)
+    }
+
+    // Add the files listed in --files-from files to the files to search
+    pub fn read_file_lists(&mut self) -> io::Result<()> {
+        for list in &self.files_from {
+            let mut contents = Vec::new();
+            if is_stdin(list) {
+                io::stdin().read_to_end(&mut contents)?;
+            } else {
+                contents = fs::read(list)?;
+            }
+            // With -0 names can contain newlines, so only NUL bytes separate them
+            let separator = if self.null_data_files { b'\0' } else { b'\n' };
+            let names = contents.split(|&byte| byte == separator).map(|name| {
+                // Lines can end with `\r\n`
+                if self.null_data_files { name } else { name.strip_suffix(b"\r").unwrap_or(name) }
+            });
+            self.filenames.extend(names.filter(|name| !name.is_empty()).map(path_from_bytes));
+        }
+        Ok(())
+    }
+
+    // Collect the --exclude globs given on the command line and in --exclude-from files
+    pub fn read_excludes(&self) -> Result<Vec<Pattern>, Box<dyn Error>> {
+        let mut globs = self.exclude.clone();
+        for file in &self.exclude_files {
+            let contents = fs::read_to_string(file)?;
+            for line in contents.lines().filter(|line| !line.is_empty()) {
+                globs.push(Pattern::new(line)?);
+            }
+        }
+        Ok(globs)
+    }
+
+    // The built-in file types along with those defined with --type-add
+    pub fn file_types(&self) -> Result<Types, Box<dyn Error>> {
+        let mut types = Types::builtin();
+        for definition in &self.type_defs {
+            types.add(definition)?;
+        }
+        Ok(types)
+    }
+
+    pub fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            case_insensitive: self.is_case_insensitive,
+            whole_line: self.whole_line,
+            fixed_strings: self.fixed_strings,
+            smart_case: self.smart_case,
+            multiline: self.multiline,
+            engine: self.engine,
+        }
+    }
+
+    pub fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }
+    }
+
+    // Whether `count` matching lines are enough to stop searching the file
+    pub fn reached_max_count(&self, count: usize) -> bool {
+        self.max_count.is_some_and(|max| count >= max)
+    }
+
+    // Whether matching lines are printed as a JSON document, or with --json-lines as a
+    // stream of JSON events. Modes that print no lines, such as -c and -l, print as usual.
+    pub fn prints_json(&self) -> bool {
+        self.json && !self.json_lines && self.prints_lines()
+    }
+
+    pub fn prints_json_lines(&self) -> bool {
+        self.json_lines && self.prints_lines()
+    }
+
+    pub fn prints_csv(&self) -> bool {
+        self.csv && self.prints_lines()
+    }
+
+    fn prints_lines(&self) -> bool {
+        !(self.quiet || self.count_only || self.files_with_matches || self.files_without_match)
+    }
+
+    pub fn collects_stats(&self) -> bool {
+        self.stats || self.stats_json
+    }
+
+    pub fn has_context(&self) -> bool {
+        self.before_context > 0 || self.after_context > 0
+    }
+}
+
+// The command line interface of a search. --no-config and --profile are handled before
+// the arguments are parsed, so they aren't part of Args.
+pub(crate) fn command() -> Command {
+    with_negations(Args::command())
+        .arg(
+            Arg::new(NO_CONFIG_ID)
+                .long(NO_CONFIG_ID)
+                .action(ArgAction::SetTrue)
+                .help("Don't read default options from ~/.config/grep-rust/config.toml or a .grep-rust.toml in the current directory or above"),
+        )
+        .arg(
+            Arg::new(PROFILE_ID)
+                .long(PROFILE_ID)
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .help("Add the options of the [profile.<NAME>] table of a configuration file (repeatable)"),
+        )
+}
+
+// Add a hidden --no-<flag> for each flag, or <flag> for one such as --no-pager, where
+// whichever comes last wins, so the command line can undo a flag a configuration file
+// turns on
+fn with_negations(mut command: Command) -> Command {
+    let flags: Vec<(String, String)> = command
+        .get_arguments()
+        .filter(|arg| matches!(arg.get_action(), ArgAction::SetTrue))
+        .filter_map(|arg| Some((arg.get_id().to_string(), arg.get_long()?.to_string())))
+        .collect();
+    for (id, long) in flags {
+        let negation = match long.strip_prefix("no-") {
+            Some(flag) => flag.to_string(),
+            None => format!("no-{}", long),
+        };
+        // Pairs such as --heading and --no-heading are already declared together
+        if command.get_arguments().any(|arg| arg.get_long() == Some(negation.as_str())) {
+            continue;
+        }
+        command = command.mut_arg(&id, |arg| arg.overrides_with(negation.clone())).arg(
+            Arg::new(negation.clone())
+                .long(negation)
+                .action(ArgAction::SetTrue)
+                .overrides_with(id)
+                .hide(true),
+        );
+    }
+    command
+}
+
+// Clap suggests the closest long option to a misspelled one, but nothing for an unknown
+// short flag, so suggest the flag that differs only in case, such as -n for -N
+pub(crate) fn suggest_flag(mut error: clap::Error, command: &Command) -> clap::Error {
+    if error.kind() != ErrorKind::UnknownArgument || error.get(ContextKind::SuggestedArg).is_some() {
+        return error;
+    }
+    let Some(ContextValue::String(arg)) = error.get(ContextKind::InvalidArg) else {
+        return error;
+    };
+    let mut chars = arg.chars();
+    let (Some('-'), Some(flag), None) = (chars.next(), chars.next(), chars.next()) else {
+        return error;
+    };
+    let other_case = if flag.is_lowercase() { flag.to_ascii_uppercase() } else { flag.to_ascii_lowercase() };
+    if command.get_arguments().any(|arg| arg.get_short() == Some(other_case)) {
+        error.insert(ContextKind::SuggestedArg, ContextValue::String(format!("-{}", other_case)));
+    }
+    error
+}
+
+// Parse a size such as `512`, `64K`, `10M` or `2G`, where the suffixes are powers of 1024
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (digits, multiplier) = match value.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&value[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&value[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    let size: u64 = digits.parse().map_err(|_| "expected a number of bytes, optionally ending in K, M or G".to_string())?;
+    size.checked_mul(multiplier).ok_or_else(|| "size is too large".to_string())
+}
+
+fn parse_tab_width(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) | Err(_) => Err("expected a width of at least 1".to_string()),
+        Ok(width) => Ok(width),
+    }
+}
+
+fn parse_glob(value: &str) -> Result<Pattern, String> {
+    Pattern::new(value).map_err(|e| e.to_string())
+}
+
+fn parse_template(value: &str) -> Result<Template, String> {
+    Template::parse(value).ok_or_else(|| "unknown placeholder or unbalanced brace".to_string())
+}
+
+fn parse_color_spec(value: &str) -> Result<String, String> {
+    match ColorSpecs::default().apply(value) {
+        Some(()) => Ok(value.to_string()),
+        None => Err("expected part:fg:color, part:bg:color, part:style:name or part:none".to_string()),
+    }
+}
+
+fn parse_theme(value: &str) -> Result<ColorSpecs, String> {
+    ColorSpecs::theme(value).ok_or_else(|| "expected 'dark', 'light' or 'solarized'".to_string())
+}
+
+// Without an explicit --color, NO_COLOR turns colors off and CLICOLOR_FORCE turns them on
+// even when not writing to a terminal, with NO_COLOR winning if both are set
+fn auto_color() -> bool {
+    if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        false
+    } else if env::var_os("CLICOLOR_FORCE").is_some_and(|value| !value.is_empty() && value != "0") {
+        true
+    } else {
+        io::stdout().is_terminal()
+    }
+}
+
+fn parse_engine(value: &str) -> Result<Engine, String> {
+    match value {
+        "default" => Ok(Engine::Default),
+        "fancy" => Ok(Engine::Fancy),
+        _ => Err("expected 'default' or 'fancy'".to_string()),
+    }
+}
+
+// Accepts the standard labels such as `latin1`, `utf-16le` or `shift_jis`, along with
+// the common spellings without a dash
+fn parse_encoding(value: &str) -> Result<ForcedEncoding, String> {
+    let label = match value.to_ascii_lowercase().as_str() {
+        "auto" => return Ok(None),
+        "utf8" => "utf-8".to_string(),
+        "utf16" | "utf16le" => "utf-16le".to_string(),
+        "utf16be" => "utf-16be".to_string(),
+        label => label.to_string(),
+    };
+    Encoding::for_label(label.as_bytes()).map(Some).ok_or_else(|| "unknown encoding".to_string())
+}
@@ -0,0 +1,131 @@
+// Default options read from configuration files and GREP_RUST_OPTS, which are given to a
+// search ahead of its command line arguments so that those take precedence
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+use crate::config;
+
+// Name of the option that skips the configuration files, without the dashes
+pub const NO_CONFIG_ID: &str = "no-config";
+
+// Name of the option that adds the options of a profile, such as `[profile.logs]`
+pub const PROFILE_ID: &str = "profile";
+
+// A project's own settings, looked for in the current directory and those above it
+const PROJECT_CONFIG: &str = ".grep-rust.toml";
+
+// Options for every search, such as those set for a whole CI image, quoted as in a shell
+const OPTIONS_VAR: &str = "GREP_RUST_OPTS";
+
+// Put the options from the user's configuration file, the project's, GREP_RUST_OPTS and
+// the profiles selected with --profile, in that order, after the program name. The files
+// are skipped if --no-config is given, which leaves no profiles.
+pub fn with_defaults(args: &[OsString]) -> Result<Vec<OsString>, Box<dyn Error>> {
+    let env_options = match env::var(OPTIONS_VAR) {
+        Ok(options) => shlex::split(&options).ok_or_else(|| format!("{}: unbalanced quotes", OPTIONS_VAR))?,
+        Err(_) => Vec::new(),
+    };
+
+    // Arguments that aren't UTF-8 can't be options
+    let cli_options = args.iter().skip(1).take_while(|arg| *arg != "--").filter_map(|arg| arg.to_str());
+    let options: Vec<&str> = env_options.iter().map(String::as_str).chain(cli_options).collect();
+    let no_config = options.iter().any(|arg| *arg == format!("--{}", NO_CONFIG_ID));
+
+    let mut defaults = Vec::new();
+    // Each profile's settings along with the file they came from, where a project's
+    // profile replaces the user's of the same name
+    let mut profiles = BTreeMap::new();
+    if !no_config {
+        for file in user_config().into_iter().chain(project_config()) {
+            let mut table = parse_config(&file)?;
+            match table.remove(PROFILE_ID) {
+                Some(Value::Table(file_profiles)) => {
+                    for (name, settings) in file_profiles {
+                        profiles.insert(name, (file.clone(), settings));
+                    }
+                }
+                Some(_) => return Err(format!("{}: `{}` must be a table", file.display(), PROFILE_ID).into()),
+                None => {}
+            }
+            defaults.extend(config_options(&file, &table)?);
+        }
+    }
+    defaults.extend(env_options.iter().cloned());
+
+    for name in profile_names(&options) {
+        match profiles.get(name) {
+            Some((file, Value::Table(settings))) => defaults.extend(config_options(file, settings)?),
+            Some((file, _)) => return Err(format!("{}: profile `{}` must be a table", file.display(), name).into()),
+            None => return Err(format!("unknown profile `{}`", name).into()),
+        }
+    }
+
+    let mut args = args.to_vec();
+    let program = if args.is_empty() { 0 } else { 1 };
+    args.splice(program..program, defaults.into_iter().map(OsString::from));
+    Ok(args)
+}
+
+// $XDG_CONFIG_HOME/grep-rust/config.toml, by default under ~/.config
+fn user_config() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("grep-rust").join("config.toml")).filter(|file| file.is_file())
+}
+
+fn project_config() -> Option<PathBuf> {
+    let dir = env::current_dir().ok()?;
+    dir.ancestors().map(|dir| dir.join(PROJECT_CONFIG)).find(|file| file.is_file())
+}
+
+// The profiles given as `--profile <name>` or `--profile=<name>`
+fn profile_names<'a>(options: &[&'a str]) -> Vec<&'a str> {
+    let flag = format!("--{}", PROFILE_ID);
+    let mut names = Vec::new();
+    for (i, option) in options.iter().enumerate() {
+        if *option == flag {
+            names.extend(options.get(i + 1).copied());
+        } else if let Some(name) = option.strip_prefix(&flag).and_then(|rest| rest.strip_prefix('=')) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+fn parse_config(file: &Path) -> Result<Table, Box<dyn Error>> {
+    Ok(fs::read_to_string(file)?.parse().map_err(|e| format!("{}: {}", file.display(), e))?)
+}
+
+// Turn the settings from a file into options. Keys are the long names of options, such
+// as `smart-case = true`, `color = "always"` or `exclude = ["*.min.js", "*.map"]`, where
+// false leaves a flag off and a list gives an option once per item.
+fn config_options(file: &Path, table: &Table) -> Result<Vec<String>, Box<dyn Error>> {
+    let command = config::command();
+    let mut args = Vec::new();
+
+    for (key, value) in table {
+        if !command.get_arguments().any(|arg| arg.get_long() == Some(key)) {
+            return Err(format!("{}: unknown option `{}`", file.display(), key).into());
+        }
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => args.push(format!("--{}", key)),
+                Value::Boolean(false) => {}
+                Value::String(text) => args.push(format!("--{}={}", key, text)),
+                Value::Integer(number) => args.push(format!("--{}={}", key, number)),
+                _ => return Err(format!("{}: unsupported value for `{}`", file.display(), key).into()),
+            }
+        }
+    }
+    Ok(args)
+}
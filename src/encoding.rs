@@ -0,0 +1,82 @@
+use std::io::{self, BufRead, Read};
+use encoding_rs::{CoderResult, Decoder, Encoding, UTF_8};
+
+// Size of the buffer each chunk of input is decoded into
+const CHUNK_LEN: usize = 8192;
+
+// Work out how to decode an input from the encoding given with --encoding, or from
+// its byte order mark. Returns None for input that can be searched as UTF-8.
+pub fn detect_decoder<R: BufRead>(forced: Option<&'static Encoding>, reader: &mut R) -> io::Result<Option<Decoder>> {
+    if let Some(encoding) = forced {
+        if encoding == UTF_8 {
+            return Ok(None);
+        }
+        return Ok(Some(encoding.new_decoder_with_bom_removal()));
+    }
+
+    match Encoding::for_bom(reader.fill_buf()?) {
+        Some((encoding, bom_len)) => {
+            reader.consume(bom_len);
+            if encoding == UTF_8 {
+                Ok(None)
+            } else {
+                Ok(Some(encoding.new_decoder_without_bom_handling()))
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+// Transcodes input to UTF-8 as it is read, so it can be searched like any other file.
+// Malformed sequences are replaced with U+FFFD, like invalid UTF-8 elsewhere.
+pub struct DecodeReader<R> {
+    inner: R,
+    decoder: Decoder,
+    decoded: Box<[u8]>,
+    len: usize,
+    pos: usize,
+    finished: bool,
+}
+
+impl<R: BufRead> DecodeReader<R> {
+    pub fn new(inner: R, decoder: Decoder) -> DecodeReader<R> {
+        DecodeReader {
+            inner,
+            decoder,
+            decoded: vec![0; CHUNK_LEN].into_boxed_slice(),
+            len: 0,
+            pos: 0,
+            finished: false,
+        }
+    }
+
+    // Decode the next chunk of input, returning false at the end of the input
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.finished {
+            return Ok(false);
+        }
+        let src = self.inner.fill_buf()?;
+        let last = src.is_empty();
+        let (result, read, written, _) = self.decoder.decode_to_utf8(src, &mut self.decoded, last);
+        self.inner.consume(read);
+        self.len = written;
+        self.pos = 0;
+        // At the end of the input the decoder flushes any incomplete character
+        self.finished = last && result == CoderResult::InputEmpty;
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.len {
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.len - self.pos);
+        buf[..len].copy_from_slice(&self.decoded[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use walkdir::WalkDir;
+use crate::filter::FileFilter;
+
+pub const STDIN_FILENAME: &str = "-";
+
+pub fn is_stdin(file: &Path) -> bool {
+    file.as_os_str() == STDIN_FILENAME
+}
+
+// Bytes inspected when deciding whether a file is binary
+pub const BINARY_CHECK_LEN: usize = 8192;
+
+// Treat files with a NUL byte near the start as binary, like GNU grep
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+// Counts the files that couldn't be read, reporting each on stderr when it is found so
+// the search carries on with the rest. Shared by every thread of a search.
+#[derive(Default)]
+pub struct FileErrors {
+    count: AtomicUsize,
+    // With -s the files are only counted
+    silent: bool,
+}
+
+impl FileErrors {
+    pub fn new(silent: bool) -> FileErrors {
+        FileErrors { silent, ..FileErrors::default() }
+    }
+
+    pub fn report(&self, path: &Path, error: impl Display) {
+        if !self.silent {
+            eprintln!("{}: {}", path.display(), error);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    // The result of a run that selected something if `selected`, which is an error if
+    // any file couldn't be read
+    pub fn into_result(self, selected: bool) -> Result<bool, Box<dyn Error>> {
+        match self.count() {
+            0 => Ok(selected),
+            count => Err(Box::new(FilesFailed(count))),
+        }
+    }
+}
+
+// The error a run ends with when some of its files couldn't be read. Each of them was
+// reported on its own, unless -s silenced them, so the error itself isn't printed.
+#[derive(Debug)]
+pub struct FilesFailed(pub usize);
+
+impl Display for FilesFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            1 => write!(f, "1 file couldn't be read"),
+            count => write!(f, "{} files couldn't be read", count),
+        }
+    }
+}
+
+impl Error for FilesFailed {}
+
+// A path read from a file list, which on unix can be any bytes but a NUL
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// Identifies a file however it is reached: its device and inode number, or where
+// those aren't available its canonical path
+#[cfg(unix)]
+pub type FileId = (u64, u64);
+#[cfg(not(unix))]
+pub type FileId = PathBuf;
+
+// Look up the identity of the file or directory a path leads to, following symlinks
+#[cfg(unix)]
+pub fn file_id(path: &Path) -> Option<FileId> {
+    fs::metadata(path).ok().map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn file_id(path: &Path) -> Option<FileId> {
+    fs::canonicalize(path).ok()
+}
+
+// The device a file or directory is on, where the platform reports one
+#[cfg(unix)]
+pub fn device_id(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+// Expand the file arguments into the list of files to search, leaving out what the
+// filter excludes from directories searched with -r and from wildcard matches, and
+// files already listed. Arguments and directory entries that can't be read are
+// reported to `errors` and left out.
+pub fn parse_filenames(
+    filenames: &[PathBuf],
+    recursive_search: bool,
+    filter: &FileFilter,
+    errors: &FileErrors,
+) -> Vec<PathBuf> {
+    let mut files = Vec::<PathBuf>::new();
+    for filename in filenames {
+        // `-` stands for standard input and is left for the caller to open
+        if is_stdin(filename) {
+            files.push(filename.clone());
+            continue;
+        }
+
+        // Expand a wildcard in the filename, which doesn't exist as a file of its own.
+        // Patterns have to be UTF-8.
+        if let Some(pattern) = filename.to_str().filter(|name| name.contains('*')) {
+            let paths = match glob::glob(pattern) {
+                Ok(paths) => paths,
+                Err(e) => {
+                    errors.report(filename, e);
+                    continue;
+                }
+            };
+            for path in paths {
+                match path {
+                    Ok(path) if !filter.is_excluded_file(&path) => files.push(path),
+                    Ok(_) => {}
+                    Err(e) => errors.report(e.path(), e.error()),
+                }
+            }
+            continue;
+        }
+
+        let metadata = match fs::metadata(filename) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                errors.report(filename, e);
+                continue;
+            }
+        };
+        if metadata.is_dir() {
+            if recursive_search {
+                // WalkDir's own min_depth would hide shallower directories from the filter
+                let entries = WalkDir::new(filename)
+                    .max_depth(filter.max_depth())
+                    .follow_links(filter.follows_links())
+                    .same_file_system(filter.one_file_system())
+                    .sort_by_file_name()
+                    .into_iter()
+                    // Excluded directories are pruned rather than walked, and with --follow
+                    // a directory reached again through a symlink is walked only once
+                    .filter_entry(|entry| {
+                        let is_dir = entry.file_type().is_dir();
+                        if is_dir && filter.follows_links() && !filter.first_visit(entry.path()) {
+                            return false;
+                        }
+                        entry.depth() == 0 || !filter.is_excluded(entry.path(), is_dir)
+                    });
+                for entry in entries {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        // A symlink back to a directory that is being walked leads nowhere
+                        // new, so it is skipped without an error, as in a parallel search
+                        Err(e) if e.loop_ancestor().is_some() && e.path().is_some_and(|path| !filter.first_visit(path)) => {
+                            continue;
+                        }
+                        Err(e) => {
+                            let path = e.path().unwrap_or(filename);
+                            errors.report(path, e.io_error().map_or(e.to_string(), ToString::to_string));
+                            continue;
+                        }
+                    };
+                    if entry.depth() >= filter.min_depth() && entry.path().is_file() {
+                        files.push(entry.into_path());
+                    }
+                }
+            } else {
+                errors.report(filename, "is a directory, use -r to search it recursively");
+            }
+        } else {
+            // Check if file exists
+            files.push(filename.clone());
+        }
+    }
+    // A file reached by more than one path, e.g. named on its own and inside a directory
+    // given with -r, is only searched the first time
+    files.retain(|file| is_stdin(file) || filter.first_visit(file));
+    files
+}
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use glob::Pattern;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use crate::config::Config;
+use crate::files::{file_id, FileId};
+
+// Files in each directory whose rules decide what a recursive search skips. `.ignore`
+// works outside git repositories, and `.grepignore` holds rules meant only for this tool.
+// Rules from later files take precedence when they disagree.
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".grepignore"];
+
+// Directories a recursive search never descends into unless filtering is turned off,
+// whether or not an ignore file mentions them
+const VCS_DIRS: [&str; 4] = [".git", ".hg", ".svn", "node_modules"];
+
+// Decides which files and directories a recursive search leaves out. It is shared by
+// all the threads walking directories, so ignore files are only read once.
+pub struct FileFilter {
+    // Levels below a directory given on the command line whose files are searched
+    max_depth: usize,
+    min_depth: usize,
+    follow: bool,
+    one_file_system: bool,
+    // Files and directories reached so far, so a file named twice or reached through a
+    // link is searched once, and a symlink loop is only walked once with --follow
+    visited: Mutex<HashSet<FileId>>,
+    hidden: bool,
+    respect_ignore: bool,
+    // Globs from --include, one of which a file's name must match
+    include: Vec<Pattern>,
+    // Globs of the file types chosen with --type, one of which a file's name must match
+    types: Vec<Pattern>,
+    // Globs from --exclude and --exclude-from, which a file's name must not match
+    exclude: Vec<Pattern>,
+    // Globs from --exclude-dir, for directories whose whole subtree is skipped
+    exclude_dir: Vec<Pattern>,
+    // The ignore rules of each directory seen so far, or None where there are none
+    ignores: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl FileFilter {
+    pub fn new(config: &Config) -> Result<FileFilter, Box<dyn Error>> {
+        Ok(FileFilter {
+            max_depth: config.max_depth.unwrap_or(usize::MAX),
+            min_depth: config.min_depth,
+            follow: config.follow,
+            one_file_system: config.one_file_system,
+            visited: Mutex::new(HashSet::new()),
+            // --no-ignore turns off all of the default filtering, hidden files included
+            hidden: config.hidden || config.no_ignore,
+            respect_ignore: !config.no_ignore,
+            include: config.include.clone(),
+            types: config.file_types()?.globs(&config.types)?,
+            exclude: config.read_excludes()?,
+            exclude_dir: config.exclude_dir.clone(),
+            ignores: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // The default filter, also leaving out files whose names match one of the globs
+    pub fn excluding(exclude: Vec<Pattern>) -> FileFilter {
+        FileFilter { exclude, ..FileFilter::default() }
+    }
+
+    // Depths count levels below the directory given on the command line, so its own
+    // files are at depth 1
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn min_depth(&self) -> usize {
+        self.min_depth
+    }
+
+    // Whether symlinks to directories are walked like the directories they point to
+    pub fn follows_links(&self) -> bool {
+        self.follow
+    }
+
+    // Whether directories on another device than the directory given on the command
+    // line, such as mount points, are left out
+    pub fn one_file_system(&self) -> bool {
+        self.one_file_system
+    }
+
+    // Record that a file is being searched or a directory walked, returning false if it
+    // already was, by this path or another
+    pub fn first_visit(&self, dir: &Path) -> bool {
+        match file_id(dir) {
+            Some(id) => self.visited.lock().unwrap().insert(id),
+            None => true,
+        }
+    }
+
+    // Whether an entry found while walking a directory should be skipped. The
+    // directories given on the command line are always searched.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        (!is_dir && (!self.is_included(path) || self.is_excluded_file(path)))
+            || (is_dir && matches_name(&self.exclude_dir, path))
+            || (!self.hidden && is_hidden(path))
+            || (self.respect_ignore && ((is_dir && is_vcs_dir(path)) || self.is_ignored(path, is_dir)))
+    }
+
+    // Files are only searched if they match an --include glob and are of one of the
+    // types chosen with --type, when any were given
+    fn is_included(&self, path: &Path) -> bool {
+        (self.include.is_empty() || matches_name(&self.include, path))
+            && (self.types.is_empty() || matches_name(&self.types, path))
+    }
+
+    // Whether a file matches an --exclude glob. Unlike the other rules this also applies
+    // to the files a wildcard argument expands to.
+    pub fn is_excluded_file(&self, path: &Path) -> bool {
+        matches_name(&self.exclude, path)
+    }
+
+    // The closest ignore file with a rule for the path decides, so a nested one can
+    // re-include what its parent ignores with `!pattern`
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for dir in path.ancestors().skip(1) {
+            let Some(rules) = self.rules(dir) else {
+                continue;
+            };
+            match rules.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+
+    fn rules(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let mut ignores = self.ignores.lock().unwrap();
+        ignores.entry(dir.to_path_buf()).or_insert_with(|| read_rules(dir)).clone()
+    }
+}
+
+impl Default for FileFilter {
+    fn default() -> FileFilter {
+        FileFilter {
+            max_depth: usize::MAX,
+            min_depth: 0,
+            follow: false,
+            one_file_system: false,
+            visited: Mutex::new(HashSet::new()),
+            hidden: false,
+            respect_ignore: true,
+            include: Vec::new(),
+            types: Vec::new(),
+            exclude: Vec::new(),
+            exclude_dir: Vec::new(),
+            ignores: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+// Dotfiles and dot-directories such as `.git` are hidden
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+// Globs are matched against the file name alone, like GNU grep's --include, --exclude and --exclude-dir
+fn matches_name(globs: &[Pattern], path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy();
+        globs.iter().any(|glob| glob.matches(&name))
+    })
+}
+
+fn is_vcs_dir(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| VCS_DIRS.iter().any(|dir| name == *dir))
+}
+
+// Read the ignore files in a directory. Lines that fail to parse are skipped.
+fn read_rules(dir: &Path) -> Option<Arc<Gitignore>> {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut found = false;
+    for name in IGNORE_FILES {
+        let file = dir.join(name);
+        if file.is_file() {
+            builder.add(file);
+            found = true;
+        }
+    }
+    if !found {
+        return None;
+    }
+    builder.build().ok().map(Arc::new)
+}
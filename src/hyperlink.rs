@@ -0,0 +1,36 @@
+// OSC 8 hyperlinks around file names, so terminals that support them can open a result
+use std::path::{self, Path};
+
+// Used by a bare --hyperlink
+pub const DEFAULT_HYPERLINK_FORMAT: &str = "file://{path}";
+
+// Build the link for a file from a format such as `vscode://file{path}:{line}:{column}`,
+// where the path is made absolute and percent-encoded. Returns None if the path can't
+// be made absolute.
+pub fn file_url(format: &str, file: &str, line: usize, column: usize) -> Option<String> {
+    let path = path::absolute(Path::new(file)).ok()?;
+    Some(
+        format
+            .replace("{path}", &encode_path(&path.to_string_lossy()))
+            .replace("{line}", &line.to_string())
+            .replace("{column}", &column.to_string()),
+    )
+}
+
+// Wrap text in an OSC 8 escape sequence linking it to the url
+pub fn wrap(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+// Percent-encode everything in a path but unreserved characters and separators
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
@@ -0,0 +1,344 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use flate2::bufread::MultiGzDecoder;
+use memmap2::Mmap;
+
+pub mod archive;
+pub mod color;
+pub mod commands;
+pub mod completions;
+pub mod config;
+pub mod defaults;
+pub mod encoding;
+pub mod files;
+pub mod filter;
+pub mod hyperlink;
+pub mod matcher;
+pub mod pager;
+pub mod parallel;
+pub mod printer;
+pub mod progress;
+pub mod replace;
+pub mod search;
+pub mod searcher;
+pub mod stats;
+pub mod template;
+pub mod types;
+
+pub use commands::Command;
+pub use completions::{run_completions, CompletionsConfig};
+pub use config::Config;
+pub use files::parse_filenames;
+pub use filter::FileFilter;
+pub use matcher::{Engine, LiteralMatcher, MatchOptions, Matcher, MultiLiteralMatcher, PatternMatcher, RegexMatcher};
+pub use parallel::search_parallel;
+pub use printer::Printer;
+pub use replace::{run_replace, ReplaceConfig};
+pub use search::{count_matches, decode, has_match, search, search_multiline, search_reader, Match};
+pub use searcher::{FileMatch, Searcher, SearcherBuilder};
+pub use types::Types;
+
+use archive::{is_gzipped, is_tar_archive, is_zip_archive, search_tar, search_zip};
+use encoding::{detect_decoder, DecodeReader};
+use pager::Output;
+use files::{is_binary, is_stdin, FileErrors, STDIN_FILENAME};
+use stats::{CountingReader, Stats};
+use printer::{filename_terminator, print_footer, print_header};
+
+// Name shown for standard input in place of a filename
+pub(crate) const STDIN_LABEL: &str = "(standard input)";
+
+// Files smaller than this are read normally even with --mmap, as mapping them costs more than copying
+const MMAP_MIN_LEN: u64 = 64 * 1024;
+
+// Run the search described by the config, returning whether anything was selected
+pub fn run(mut config: Config) -> Result<bool, Box<dyn Error>> {
+    // The colored crate makes its own guess about the terminal, which --color overrides
+    colored::control::set_override(config.coloured_output);
+
+    config.read_file_lists()?;
+
+    if config.type_list {
+        let mut out = Output::new(&config);
+        for (name, globs) in config.file_types()?.iter() {
+            writeln!(out, "{}: {}", name, globs.join(", "))?;
+        }
+        return Ok(true);
+    }
+
+    if config.list_files {
+        let mut out = Output::new(&config);
+        let errors = FileErrors::new(config.no_messages);
+        let files = input_files(&config, &errors)?;
+        for file in &files {
+            write!(out, "{}{}", file.display(), filename_terminator(&config))?;
+        }
+        return errors.into_result(!files.is_empty());
+    }
+
+    let matcher = PatternMatcher::new(&config.read_patterns()?, &config.match_options())?;
+
+    let mut out = Output::new(&config);
+
+    // Directories and multiple files are searched in parallel. A single input is printed
+    // as it is searched, so piped input keeps streaming, and stdin is read when -r is
+    // given without any files.
+    let threads = config.thread_count();
+    let result = if threads > 1 && !config.filenames.is_empty() && (config.recursive_search || config.filenames.len() > 1) {
+        search_parallel(&config, &matcher, threads, &mut out)
+    } else {
+        search_sequential(&config, &matcher, &mut out)
+    };
+
+    // The reader of the results going away, such as `head` or a pager being quit, closes
+    // the pipe, which ends the search quietly rather than being an error
+    match result {
+        Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) => Ok(true),
+        result => result,
+    }
+}
+
+// Search the inputs one at a time on this thread, printing to `out`. A file that can't be
+// read is reported and skipped.
+fn search_sequential<W: Write>(config: &Config, matcher: &dyn Matcher, out: &mut W) -> Result<bool, Box<dyn Error>> {
+    let started = Instant::now();
+    let stats = Stats::new(config.collects_stats());
+    let errors = FileErrors::new(config.no_messages);
+    let files = input_files(config, &errors)?;
+    let mut searched_files = 0;
+    let mut matched_files = 0;
+
+    print_header(config, out)?;
+    let mut printer = Printer::new(config, matcher, &stats, &mut *out);
+
+    // Open the files
+    for file in files {
+        printer.begin_file();
+
+        let matched = match search_input(config, matcher, &mut printer, &file) {
+            Ok(matched) => matched,
+            // Failing to write the results ends the search
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Err(e.into()),
+            Err(e) => {
+                errors.report(&file, e);
+                continue;
+            }
+        };
+        searched_files += 1;
+        matched_files += usize::from(matched);
+
+        // With -q the first match settles the result, so skip the remaining files
+        if matched && config.quiet {
+            break;
+        }
+    }
+
+    print_footer(config, printer.into_inner(), &stats, started.elapsed(), searched_files, matched_files)?;
+    finish(config, errors, matched_files > 0)
+}
+
+// The result of a search: whether anything was selected, or an error if some files
+// couldn't be read. As with GNU grep, a match found with -q outweighs the errors.
+pub(crate) fn finish(config: &Config, errors: FileErrors, matched: bool) -> Result<bool, Box<dyn Error>> {
+    if config.quiet && matched {
+        return Ok(true);
+    }
+    errors.into_result(matched)
+}
+
+// Get the files to search, reading stdin if none were given either as arguments or with
+// --files-from
+fn input_files(config: &Config, errors: &FileErrors) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if config.filenames.is_empty() && config.files_from.is_empty() {
+        return Ok(vec![PathBuf::from(STDIN_FILENAME)]);
+    }
+    Ok(parse_filenames(&config.filenames, config.recursive_search, &FileFilter::new(config)?, errors))
+}
+
+// Search a file, or stdin for `-`
+pub(crate) fn search_input<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    printer: &mut Printer<W>,
+    file: &Path,
+) -> io::Result<bool> {
+    if is_stdin(file) {
+        search_source(config, matcher, printer, STDIN_LABEL, io::stdin().lock())
+    } else {
+        search_file(config, matcher, printer, file)
+    }
+}
+
+// Search a file unless it is larger than --max-filesize, looking inside it with -z if
+// it is an archive or gzipped, through a memory map if --mmap was given and the file is
+// large enough for it to pay off, and otherwise line by line so memory use doesn't
+// depend on its size. A name that isn't UTF-8 is shown with replacement characters.
+fn search_file<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    printer: &mut Printer<W>,
+    path: &Path,
+) -> io::Result<bool> {
+    let handle = File::open(path)?;
+    let name = path.to_string_lossy();
+
+    if let Some(max) = config.max_filesize {
+        if handle.metadata()?.len() > max {
+            printer.stats().add_skipped();
+            if config.verbose {
+                eprintln!("{}: file larger than --max-filesize skipped", name);
+            }
+            return Ok(false);
+        }
+    }
+
+    if config.search_zip && is_zip_archive(&name) {
+        return search_zip(config, matcher, printer, &name, handle);
+    }
+
+    if config.search_zip && is_tar_archive(&name) {
+        return if is_gzipped(&name) {
+            search_tar(config, matcher, printer, &name, MultiGzDecoder::new(BufReader::new(handle)))
+        } else {
+            search_tar(config, matcher, printer, &name, handle)
+        };
+    }
+
+    if config.search_zip && is_gzipped(&name) {
+        // Multi-member archives, such as logs appended to after compression, are read in full
+        let reader = BufReader::new(MultiGzDecoder::new(BufReader::new(handle)));
+        return search_source(config, matcher, printer, &name, reader);
+    }
+
+    if config.mmap {
+        let metadata = handle.metadata()?;
+        if metadata.is_file() && metadata.len() >= MMAP_MIN_LEN {
+            // Safety: the map is only read, and like other grep tools we accept that a file
+            // truncated by another process while it is being searched can crash the search
+            let map = unsafe { Mmap::map(&handle)? };
+            return search_source(config, matcher, printer, &name, &map[..]);
+        }
+    }
+
+    search_source(config, matcher, printer, &name, BufReader::new(handle))
+}
+
+// Search one input, first checking how it is encoded and skipping it if it is binary
+pub(crate) fn search_source<R: BufRead, W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    printer: &mut Printer<W>,
+    name: &str,
+    mut reader: R,
+) -> io::Result<bool> {
+    // UTF-16 text is full of NUL bytes, so the encoding is worked out before the binary
+    // check, and input in another encoding is transcoded rather than checked
+    if let Some(decoder) = detect_decoder(config.encoding, &mut reader)? {
+        let reader = BufReader::new(DecodeReader::new(reader, decoder));
+        return search_text(config, matcher, printer, name, reader);
+    }
+
+    // Look at the start of the input without consuming it
+    if !config.text && is_binary(reader.fill_buf()?) {
+        printer.stats().add_skipped();
+        if config.verbose {
+            eprintln!("{}: binary file skipped", name);
+        }
+        return Ok(false);
+    }
+
+    search_text(config, matcher, printer, name, reader)
+}
+
+// Search one input and print the results in the mode selected by the config.
+// Matches are printed as each line is read, so piped input is streamed.
+// Returns whether the input contained any selected line, or for -L whether the file was listed.
+fn search_text<R: BufRead, W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    printer: &mut Printer<W>,
+    name: &str,
+    reader: R,
+) -> io::Result<bool> {
+    let stats = printer.stats();
+    let mut reader = CountingReader::new(reader, stats);
+
+    if config.multiline {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let contents = decode(bytes);
+        let matches = search_multiline(config, matcher, &contents);
+        stats.add_searched(matches.len());
+        return report_matches(config, printer, name, matches);
+    }
+
+    // Modes that stop at the first match count it as the only matched line
+    if config.quiet {
+        let matched = has_match(config, matcher, reader)?;
+        stats.add_searched(usize::from(matched));
+        return Ok(matched);
+    }
+
+    if config.files_with_matches || config.files_without_match {
+        let matched = has_match(config, matcher, reader)?;
+        stats.add_searched(usize::from(matched));
+        // -L prints exactly the files that -l would not
+        let listed = matched != config.files_without_match;
+        if listed {
+            printer.print_filename(name)?;
+        }
+        return Ok(listed);
+    }
+
+    if config.count_only {
+        let count = count_matches(config, matcher, reader)?;
+        stats.add_searched(count);
+        printer.print_count(name, count)?;
+        return Ok(count > 0);
+    }
+
+    let mut matched_lines = 0;
+    search_reader(config, matcher, reader, |m| {
+        matched_lines += usize::from(!m.is_context);
+        printer.print(name, &m)
+    })?;
+    stats.add_searched(matched_lines);
+    printer.end_file(name)?;
+    Ok(matched_lines > 0)
+}
+
+// Print results that were collected up front, in the mode selected by the config
+fn report_matches<W: Write>(
+    config: &Config,
+    printer: &mut Printer<W>,
+    name: &str,
+    matches: Vec<Match>,
+) -> io::Result<bool> {
+    let matched = !matches.is_empty();
+
+    if config.quiet {
+        return Ok(matched);
+    }
+
+    if config.files_with_matches || config.files_without_match {
+        let listed = matched != config.files_without_match;
+        if listed {
+            printer.print_filename(name)?;
+        }
+        return Ok(listed);
+    }
+
+    if config.count_only {
+        printer.print_count(name, matches.len())?;
+        return Ok(matched);
+    }
+
+    for m in &matches {
+        printer.print(name, m)?;
+    }
+    printer.end_file(name)?;
+    Ok(matched)
+}
@@ -0,0 +1,403 @@
+use std::cmp::Reverse;
+use std::error::Error;
+use std::str;
+use std::sync::LazyLock;
+use aho_corasick::{AhoCorasick, Input, MatchKind};
+use memchr::memmem::Finder;
+use regex::bytes::{Regex, RegexBuilder};
+use regex_syntax::hir::literal::Extractor;
+use regex_syntax::ParserBuilder;
+use crate::search::decode;
+
+// Regex engine used to compile patterns
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Engine {
+    // The `regex` crate, which runs in linear time but has no lookaround or backreferences
+    #[default]
+    Default,
+    // `fancy-regex`, which adds lookaround and backreferences and hands everything
+    // else to the default engine
+    Fancy,
+}
+
+// Options controlling how a pattern is compiled
+#[derive(Clone, Default)]
+pub struct MatchOptions {
+    pub case_insensitive: bool,
+    // The pattern must match the entire line
+    pub whole_line: bool,
+    // Treat patterns as literal strings instead of regular expressions
+    pub fixed_strings: bool,
+    // Ignore case unless the pattern contains an uppercase character
+    pub smart_case: bool,
+    // Patterns are matched against a whole buffer, so `^` and `$` match at line boundaries,
+    // which may be `\r\n` as well as `\n`
+    pub multiline: bool,
+    pub engine: Engine,
+}
+
+// Finds matches in a line, or with -U in a whole buffer, so the search loop doesn't care
+// how they are found. Offsets are byte ranges, and in text that is valid UTF-8 they must
+// fall on character boundaries, as the printer slices lines with them. Matchers are
+// shared by the threads of a parallel search.
+pub trait Matcher: Send + Sync {
+    // Find the first match that starts at or after byte offset `start`
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)>;
+
+    fn find(&self, haystack: &[u8]) -> Option<(usize, usize)> {
+        self.find_at(haystack, 0)
+    }
+
+    fn is_match(&self, haystack: &[u8]) -> bool {
+        self.find(haystack).is_some()
+    }
+
+    // Return the byte ranges of all non-overlapping, non-empty matches
+    fn find_iter(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start <= haystack.len() {
+            let Some((match_start, match_end)) = self.find_at(haystack, start) else {
+                break;
+            };
+            if match_start == match_end {
+                // Step over empty matches one character at a time so the search makes progress
+                start = match_end + haystack.get(match_end).map_or(1, |&byte| utf8_len(byte));
+                continue;
+            }
+            matches.push((match_start, match_end));
+            start = match_end;
+        }
+        matches
+    }
+
+    // Append the replacement for the match starting at `start`. The replacement is used
+    // as is unless the matcher has capture groups, or the whole match as `$0`, to fill in.
+    fn expand_at(&self, _haystack: &[u8], _start: usize, replacement: &str, output: &mut Vec<u8>) {
+        output.extend_from_slice(replacement.as_bytes());
+    }
+
+    // Return the byte range of each match in the line along with its expanded replacement
+    fn replace_iter(&self, line: &str, replacement: &str) -> Vec<(usize, usize, String)> {
+        self.find_iter(line.as_bytes())
+            .into_iter()
+            .map(|(start, end)| {
+                let mut replaced = Vec::new();
+                self.expand_at(line.as_bytes(), start, replacement, &mut replaced);
+                (start, end, decode(replaced))
+            })
+            .collect()
+    }
+
+    // Rewrite every match in the line using the replacement
+    fn replace_all(&self, line: &str, replacement: &str) -> String {
+        let mut output = String::new();
+        let mut last = 0;
+        for (start, end, replaced) in self.replace_iter(line, replacement) {
+            output.push_str(&line[last..start]);
+            output.push_str(&replaced);
+            last = end;
+        }
+        output.push_str(&line[last..]);
+        output
+    }
+}
+
+// The length of the UTF-8 sequence a byte starts, or 1 for a byte that starts none
+fn utf8_len(byte: u8) -> usize {
+    match byte {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    }
+}
+
+// A case-sensitive fixed string, found with a vectorized substring search
+pub struct LiteralMatcher {
+    finder: Finder<'static>,
+    // The string must make up the whole haystack, as with -x
+    whole_line: bool,
+}
+
+impl LiteralMatcher {
+    pub fn new(literal: &str, whole_line: bool) -> LiteralMatcher {
+        LiteralMatcher { finder: Finder::new(literal).into_owned(), whole_line }
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        let needle = self.finder.needle();
+        if self.whole_line {
+            return (start == 0 && haystack == needle).then_some((0, haystack.len()));
+        }
+        self.finder
+            .find(&haystack[start..])
+            .map(|offset| (start + offset, start + offset + needle.len()))
+    }
+
+    fn expand_at(&self, haystack: &[u8], start: usize, replacement: &str, output: &mut Vec<u8>) {
+        expand_literal(self, haystack, start, replacement, output);
+    }
+}
+
+// Several case-sensitive fixed strings searched together in a single pass. Where more
+// than one matches at the same position the longest wins.
+pub struct MultiLiteralMatcher {
+    automaton: AhoCorasick,
+}
+
+impl MultiLiteralMatcher {
+    pub fn new<T: AsRef<[u8]>>(literals: &[T]) -> Result<MultiLiteralMatcher, Box<dyn Error>> {
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(literals)?;
+        Ok(MultiLiteralMatcher { automaton })
+    }
+}
+
+impl Matcher for MultiLiteralMatcher {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        self.automaton
+            .find(Input::new(haystack).span(start..haystack.len()))
+            .map(|m| (m.start(), m.end()))
+    }
+
+    fn expand_at(&self, haystack: &[u8], start: usize, replacement: &str, output: &mut Vec<u8>) {
+        expand_literal(self, haystack, start, replacement, output);
+    }
+}
+
+// Matches anything, so the matched text of a literal can be expanded as the whole match
+static WHOLE_MATCH: LazyLock<Regex> = LazyLock::new(|| Regex::new("(?s-u:.*)").unwrap());
+
+// Expand a replacement for the match of a literal at `start`, with the same syntax as for
+// a regex: `$0` or `${0}` is the matched text, `$$` a dollar sign, and any other group is
+// empty, as a literal has none
+fn expand_literal(matcher: &dyn Matcher, haystack: &[u8], start: usize, replacement: &str, output: &mut Vec<u8>) {
+    if let Some((start, end)) = matcher.find_at(haystack, start) {
+        if let Some(captures) = WHOLE_MATCH.captures(&haystack[start..end]) {
+            captures.expand(replacement.as_bytes(), output);
+        }
+    }
+}
+
+// A regular expression compiled with the engine and options of a search, where `$1` or
+// `${name}` in a replacement refer to its capture groups
+pub struct RegexMatcher {
+    regex: CompiledRegex,
+}
+
+enum CompiledRegex {
+    // Along with the literals every match must start with, which are scanned for first
+    // so most lines never reach the regex engine
+    Default { regex: Regex, prefilter: Option<AhoCorasick> },
+    // Only searches UTF-8 text
+    Fancy(fancy_regex::Regex),
+}
+
+impl RegexMatcher {
+    // Compile a pattern, which with -F is taken literally and with -x has to match the
+    // whole line. The regex engine handles case-insensitivity.
+    pub fn new(pattern: &str, options: &MatchOptions) -> Result<RegexMatcher, Box<dyn Error>> {
+        let case_insensitive = is_case_insensitive(pattern, options);
+
+        let pattern = if options.fixed_strings {
+            regex::escape(pattern)
+        } else {
+            pattern.to_string()
+        };
+        let pattern = if options.whole_line {
+            format!("^(?:{})$", pattern)
+        } else {
+            pattern
+        };
+
+        if options.engine == Engine::Fancy {
+            let regex = fancy_regex::RegexBuilder::new(&pattern)
+                .case_insensitive(case_insensitive)
+                .multi_line(options.multiline)
+                .crlf(true)
+                .build()?;
+            return Ok(RegexMatcher { regex: CompiledRegex::Fancy(regex) });
+        }
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .multi_line(options.multiline)
+            .crlf(true)
+            .build()?;
+        let prefilter = prefix_literals(&pattern, case_insensitive, options.multiline);
+        Ok(RegexMatcher { regex: CompiledRegex::Default { regex, prefilter } })
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn is_match(&self, haystack: &[u8]) -> bool {
+        match &self.regex {
+            CompiledRegex::Default { regex, prefilter } => {
+                prefilter.as_ref().is_none_or(|literals| literals.is_match(haystack)) && regex.is_match(haystack)
+            }
+            // Errors such as hitting the backtracking limit count as no match
+            CompiledRegex::Fancy(regex) => {
+                str::from_utf8(haystack).is_ok_and(|text| regex.is_match(text).unwrap_or(false))
+            }
+        }
+    }
+
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        match &self.regex {
+            CompiledRegex::Default { regex, prefilter } => {
+                // A match at or after `start` must begin with one of the literals
+                let span = Input::new(haystack).span(start..haystack.len());
+                if prefilter.as_ref().is_some_and(|literals| !literals.is_match(span)) {
+                    return None;
+                }
+                regex.find_at(haystack, start).map(|m| (m.start(), m.end()))
+            }
+            CompiledRegex::Fancy(regex) => regex
+                .find_from_pos(str::from_utf8(haystack).ok()?, start)
+                .ok()
+                .flatten()
+                .map(|m| (m.start(), m.end())),
+        }
+    }
+
+    fn expand_at(&self, haystack: &[u8], start: usize, replacement: &str, output: &mut Vec<u8>) {
+        match &self.regex {
+            CompiledRegex::Default { regex, .. } => {
+                if let Some(captures) = regex.captures_at(haystack, start) {
+                    captures.expand(replacement.as_bytes(), output);
+                }
+            }
+            CompiledRegex::Fancy(regex) => {
+                let Ok(text) = str::from_utf8(haystack) else {
+                    return;
+                };
+                if let Ok(Some(captures)) = regex.captures_from_pos(text, start) {
+                    let mut expanded = String::new();
+                    captures.expand(replacement, &mut expanded);
+                    output.extend_from_slice(expanded.as_bytes());
+                }
+            }
+        }
+    }
+}
+
+// Most literal sets worth scanning for before running a regex
+const PREFILTER_MAX_LITERALS: usize = 32;
+
+// Build a prefilter from the literals that every match of the regex must start with,
+// e.g. `error: ` for `error: \d+`. Patterns that can start with anything get none.
+fn prefix_literals(pattern: &str, case_insensitive: bool, multiline: bool) -> Option<AhoCorasick> {
+    let hir = ParserBuilder::new()
+        .case_insensitive(case_insensitive)
+        .multi_line(multiline)
+        .crlf(true)
+        .build()
+        .parse(pattern)
+        .ok()?;
+    let seq = Extractor::new().extract(&hir);
+    let literals = seq.literals()?;
+    if literals.is_empty()
+        || literals.len() > PREFILTER_MAX_LITERALS
+        || literals.iter().any(|literal| literal.as_bytes().is_empty())
+    {
+        return None;
+    }
+    AhoCorasick::new(literals.iter().map(|literal| literal.as_bytes())).ok()
+}
+
+fn is_case_insensitive(pattern: &str, options: &MatchOptions) -> bool {
+    options.case_insensitive || (options.smart_case && !has_uppercase(pattern, options.fixed_strings))
+}
+
+// Whether the pattern is matched as a plain case-sensitive string, either because of -F
+// or because it has no regex syntax in it
+fn is_plain_literal(pattern: &str, options: &MatchOptions) -> bool {
+    !options.whole_line
+        && !is_case_insensitive(pattern, options)
+        && (options.fixed_strings || regex::escape(pattern) == pattern)
+}
+
+// Check a pattern for uppercase characters, skipping over regex escapes like `\S`
+fn has_uppercase(pattern: &str, fixed_strings: bool) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && !fixed_strings {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+// The patterns of a search. A line matches if any of the patterns match it, and when
+// several match at the same position the longest match wins.
+pub struct PatternMatcher {
+    patterns: Vec<Box<dyn Matcher>>,
+}
+
+impl PatternMatcher {
+    // Compile the patterns, with case-sensitive fixed strings searched for as literals.
+    // Several plain literals are combined into one Aho-Corasick automaton so each line is
+    // scanned once for all of them, rather than once per pattern.
+    pub fn new(patterns: &[String], options: &MatchOptions) -> Result<PatternMatcher, Box<dyn Error>> {
+        let (literals, others): (Vec<&String>, Vec<&String>) =
+            patterns.iter().partition(|pattern| is_plain_literal(pattern, options));
+
+        let mut compiled: Vec<Box<dyn Matcher>> = Vec::with_capacity(others.len() + 1);
+        if literals.len() > 1 {
+            compiled.push(Box::new(MultiLiteralMatcher::new(&literals)?));
+        } else {
+            for pattern in literals {
+                compiled.push(compile(pattern, options)?);
+            }
+        }
+        for pattern in others {
+            compiled.push(compile(pattern, options)?);
+        }
+
+        Ok(PatternMatcher { patterns: compiled })
+    }
+
+    // Find the first match at or after `start`, along with the index of the pattern that produced it
+    fn find_pattern_at(&self, haystack: &[u8], start: usize) -> Option<(usize, usize, usize)> {
+        self.patterns
+            .iter()
+            .enumerate()
+            .filter_map(|(index, pattern)| {
+                pattern.find_at(haystack, start).map(|(start, end)| (index, start, end))
+            })
+            .min_by_key(|&(_, start, end)| (start, Reverse(end)))
+    }
+}
+
+impl Matcher for PatternMatcher {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        self.find_pattern_at(haystack, start).map(|(_, start, end)| (start, end))
+    }
+
+    fn is_match(&self, haystack: &[u8]) -> bool {
+        self.patterns.iter().any(|pattern| pattern.is_match(haystack))
+    }
+
+    // Each match is expanded by the pattern that produced it
+    fn expand_at(&self, haystack: &[u8], start: usize, replacement: &str, output: &mut Vec<u8>) {
+        if let Some((index, _, _)) = self.find_pattern_at(haystack, start) {
+            self.patterns[index].expand_at(haystack, start, replacement, output);
+        }
+    }
+}
+
+// Compile a single pattern, as a literal if it is a case-sensitive fixed string
+fn compile(pattern: &str, options: &MatchOptions) -> Result<Box<dyn Matcher>, Box<dyn Error>> {
+    if options.fixed_strings && !is_case_insensitive(pattern, options) {
+        return Ok(Box::new(LiteralMatcher::new(pattern, options.whole_line)));
+    }
+    // Case-insensitive fixed strings are left to the regex engine, which folds case with
+    // the Unicode tables instead of lowercasing every line
+    Ok(Box::new(RegexMatcher::new(pattern, options)?))
+}
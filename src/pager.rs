@@ -0,0 +1,101 @@
+// Sending the results to a pager when writing to a terminal, as git does
+use std::env;
+use std::io::{self, BufWriter, Write};
+use std::process::{Child, Command, Stdio};
+use crate::config::Config;
+
+// Used when $PAGER isn't set
+const DEFAULT_PAGER: &str = "less -R";
+
+// Options given to less unless $LESS sets its own: quit at once if everything fits on a
+// screen, pass colors through, and leave the output on screen afterwards
+const DEFAULT_LESS: &str = "FRX";
+
+// Where search results are written: stdout, or the input of a pager. Writes are buffered
+// rather than going out line by line, unless they are line buffered. The pager is waited
+// for when the output is dropped, so it keeps the terminal until the user quits it.
+pub struct Output {
+    // Taken when the output is dropped, which closes the pipe to the pager
+    writer: Option<BufWriter<Box<dyn Write>>>,
+    pager: Option<Child>,
+    // With --line-buffered, flush whenever a line ends
+    line_buffered: bool,
+}
+
+impl Output {
+    // Start the pager selected by the config, writing to stdout if there is none or it
+    // fails to start
+    pub fn new(config: &Config) -> Output {
+        let mut pager = None;
+        let mut writer: Option<Box<dyn Write>> = None;
+        if let Some(command) = &config.pager {
+            if let Ok(mut child) = pager_command(command).stdin(Stdio::piped()).spawn() {
+                writer = child.stdin.take().map(|stdin| Box::new(stdin) as Box<dyn Write>);
+                pager = Some(child);
+            }
+        }
+        // Stdout is locked once rather than on every write
+        let writer = writer.unwrap_or_else(|| Box::new(io::stdout().lock()));
+        Output { writer: Some(BufWriter::new(writer)), pager, line_buffered: config.line_buffered }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let writer = self.writer.as_mut().ok_or(io::ErrorKind::BrokenPipe)?;
+        let written = writer.write(buf)?;
+        if self.line_buffered && buf[..written].contains(&b'\n') {
+            writer.flush()?;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        // Closing the pipe lets the pager see the end of the output
+        drop(self.writer.take());
+        if let Some(pager) = &mut self.pager {
+            let _ = pager.wait();
+        }
+    }
+}
+
+// The pager to use: $PAGER, or less. An empty $PAGER or `cat` turns paging off.
+pub fn pager_from_env() -> Option<String> {
+    match env::var("PAGER") {
+        Ok(pager) if pager.trim().is_empty() || pager.trim() == "cat" => None,
+        Ok(pager) => Some(pager),
+        Err(_) => Some(DEFAULT_PAGER.to_string()),
+    }
+}
+
+// $PAGER can hold a command line, which the shell takes care of
+#[cfg(unix)]
+fn pager_command(pager: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(pager);
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", DEFAULT_LESS);
+    }
+    command
+}
+
+#[cfg(not(unix))]
+fn pager_command(pager: &str) -> Command {
+    let mut parts = pager.split_whitespace();
+    let mut command = Command::new(parts.next().unwrap_or(pager));
+    command.args(parts);
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", DEFAULT_LESS);
+    }
+    command
+}
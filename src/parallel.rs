@@ -0,0 +1,363 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+use crate::config::Config;
+use crate::files::{device_id, parse_filenames, FileErrors};
+use crate::filter::FileFilter;
+use crate::matcher::Matcher;
+use crate::progress::{Progress, PROGRESS_INTERVAL};
+use crate::printer::{paint, print_footer, print_header, Printer, JSON_SEPARATOR};
+use crate::{finish, search_input};
+use crate::stats::Stats;
+
+// What a worker found in one file: the formatted output and whether anything matched
+type FileResult = io::Result<(Vec<u8>, bool)>;
+
+// Where a file or directory comes in the output: the position of the argument it was
+// found through, then the names leading to it from there. Sorting by it gives the order
+// of a sequential search, which walks each directory in name order.
+type Key = (usize, Vec<OsString>);
+
+// A unit of work for the pool
+enum Work {
+    // A directory to list, with how many levels below a directory argument it is and,
+    // with --one-file-system, the device of that argument
+    Dir { key: Key, path: PathBuf, depth: usize, device: Option<u64> },
+    File(Key, PathBuf),
+}
+
+impl Work {
+    fn key(&self) -> &Key {
+        match self {
+            Work::Dir { key, .. } | Work::File(key, _) => key,
+        }
+    }
+
+    fn is_file(&self) -> bool {
+        matches!(self, Work::File(..))
+    }
+}
+
+// What the workers tell the printing thread
+enum Found {
+    // The work found in a directory, which takes its place, or in the arguments
+    Listed(Option<Key>, Vec<Key>),
+    Searched(Key, PathBuf, FileResult),
+}
+
+// Work shared by all threads. Directories found by one thread can be picked up by any
+// idle thread, so a single large subtree doesn't leave the others waiting.
+struct Queue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+struct QueueState {
+    items: Vec<Work>,
+    // Threads currently processing an item, which may still add more work
+    active: usize,
+}
+
+impl Queue {
+    fn new(items: Vec<Work>) -> Queue {
+        Queue {
+            state: Mutex::new(QueueState { items, active: 0 }),
+            changed: Condvar::new(),
+        }
+    }
+
+    // Wait for the next item, or return None once the queue is empty and no thread can add to it
+    fn pop(&self) -> Option<Work> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(work) = state.items.pop() {
+                state.active += 1;
+                return Some(work);
+            }
+            if state.active == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn push(&self, items: impl DoubleEndedIterator<Item = Work>) {
+        let mut state = self.state.lock().unwrap();
+        // The queue is a stack, so push in reverse to take the first item next
+        state.items.extend(items.rev());
+        self.changed.notify_all();
+    }
+
+    // Mark an item returned by `pop` as finished
+    fn done(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.active -= 1;
+        if state.active == 0 && state.items.is_empty() {
+            self.changed.notify_all();
+        }
+    }
+
+    // Drop all remaining work so the threads wind down
+    fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.items.clear();
+        self.changed.notify_all();
+    }
+}
+
+// Search on a pool of worker threads that walk directories and search files as they are
+// found, rather than collecting the whole file list first. Each worker formats a file
+// into its own buffer, and the calling thread writes the buffers to `out` in the same
+// order as a sequential search, so lines from different files are never interleaved
+// and the output doesn't depend on the threads. Files and directories that can't be
+// read are reported and skipped.
+pub fn search_parallel<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    threads: usize,
+    out: &mut W,
+) -> Result<bool, Box<dyn Error>> {
+    let started = Instant::now();
+    let stats = Stats::new(config.collects_stats());
+    let filter = FileFilter::new(config)?;
+    let errors = FileErrors::new(config.no_messages);
+    let work = initial_work(config, &filter, &errors);
+    // Files found so far, for the progress line
+    let found = AtomicUsize::new(work.iter().filter(|work| work.is_file()).count());
+    let keys = work.iter().map(|work| work.key().clone()).collect();
+    let queue = Queue::new(work);
+    // The progress line would garble a pager's screen
+    let progress = Progress::new(config.recursive_search && !config.quiet && config.pager.is_none());
+    // Set to stop the workers early, after a -q match or an error
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel::<Found>();
+    sender.send(Found::Listed(None, keys))?;
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (queue, found, filter, errors, stats, progress, stop) =
+                (&queue, &found, &filter, &errors, &stats, &progress, &stop);
+            scope.spawn(move || {
+                while let Some(work) = queue.pop() {
+                    if !stop.load(Ordering::Relaxed) {
+                        match work {
+                            Work::Dir { key, path, depth, device } => {
+                                let work = read_dir(&key, &path, depth, device, filter, errors, progress);
+                                let files = work.iter().filter(|work| work.is_file()).count();
+                                found.fetch_add(files, Ordering::Relaxed);
+                                // The printing thread hears of the new work before any
+                                // of it can be searched
+                                let keys = work.iter().map(|work| work.key().clone()).collect();
+                                if sender.send(Found::Listed(Some(key), keys)).is_err() {
+                                    stop.store(true, Ordering::Relaxed);
+                                }
+                                queue.push(work.into_iter())
+                            }
+                            Work::File(key, file) => {
+                                progress.start_file(&file);
+                                let mut printer = Printer::new(config, matcher, stats, Vec::new());
+                                let result = search_input(config, matcher, &mut printer, &file)
+                                    .map(|matched| (printer.into_inner(), matched));
+                                progress.finish_file();
+                                if sender.send(Found::Searched(key, file, result)).is_err() {
+                                    stop.store(true, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                    }
+                    queue.done();
+                }
+            });
+        }
+        drop(sender);
+
+        let ticker = progress.is_enabled().then(|| {
+            let (progress, found, stop) = (&progress, &found, &stop);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    progress.draw(found.load(Ordering::Relaxed));
+                    thread::park_timeout(PROGRESS_INTERVAL);
+                }
+            })
+        });
+
+        let result = print_in_order(config, out, receiver, &stats, &errors, &progress, started);
+        // Let the workers finish early if printing stopped before every file was searched
+        stop.store(true, Ordering::Relaxed);
+        queue.clear();
+        if let Some(ticker) = ticker {
+            ticker.thread().unpark();
+            let _ = ticker.join();
+        }
+        // A -q match or an error can end the search with the line still showing
+        progress.clear();
+        Ok(result?)
+    })
+    .and_then(|matched| finish(config, errors, matched))
+}
+
+// Turn the file arguments into work: directories to walk with -r, and files to search
+fn initial_work(config: &Config, filter: &FileFilter, errors: &FileErrors) -> Vec<Work> {
+    let mut work = Vec::new();
+    for filename in &config.filenames {
+        if config.recursive_search && fs::metadata(filename).is_ok_and(|m| m.is_dir()) {
+            let path = filename.clone();
+            let device = if filter.one_file_system() { device_id(&path) } else { None };
+            work.push(Work::Dir { key: (work.len(), Vec::new()), path, depth: 0, device });
+            continue;
+        }
+
+        // Globs and plain files are resolved the same way as for a sequential search
+        let files = parse_filenames(slice::from_ref(filename), false, filter, errors);
+        for file in files {
+            work.push(Work::File((work.len(), Vec::new()), file));
+        }
+    }
+    // Take the first argument first
+    work.reverse();
+    work
+}
+
+// List a directory, whose entries are keyed by their names under the directory's `key`.
+// A directory that can't be read is reported to `errors`. Unreadable entries and those
+// the filter excludes are skipped, along with those outside the depth limits or, with
+// --one-file-system, on another device than `device`. Symlinks are searched if they
+// point to a file but only descended into with --follow. Files already reached by
+// another path are left out.
+fn read_dir(
+    key: &Key,
+    dir: &Path,
+    depth: usize,
+    device: Option<u64>,
+    filter: &FileFilter,
+    errors: &FileErrors,
+    progress: &Progress,
+) -> Vec<Work> {
+    if depth >= filter.max_depth() || (filter.follows_links() && !filter.first_visit(dir)) {
+        return Vec::new();
+    }
+    let depth = depth + 1;
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            progress.suspend(|| errors.report(dir, e));
+            return Vec::new();
+        }
+    };
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut work = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        let mut names = key.1.clone();
+        names.push(entry.file_name());
+        let key = (key.0, names);
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let is_dir = file_type.is_dir() || (filter.follows_links() && file_type.is_symlink() && path.is_dir());
+        if filter.is_excluded(&path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            if device.is_some() && device_id(&path) != device {
+                continue;
+            }
+            work.push(Work::Dir { key, path, depth, device });
+        } else if depth >= filter.min_depth() && path.is_file() && filter.first_visit(&path) {
+            work.push(Work::File(key, path));
+        }
+    }
+    work
+}
+
+// Write each file's output as soon as everything before it has been written, reporting
+// the files that couldn't be read in the same order
+fn print_in_order<W: Write>(
+    config: &Config,
+    out: &mut W,
+    receiver: mpsc::Receiver<Found>,
+    stats: &Stats,
+    errors: &FileErrors,
+    progress: &Progress,
+    started: Instant,
+) -> io::Result<bool> {
+    // The work that is still to be listed or printed, and files searched ahead of their turn
+    let mut outstanding = BTreeSet::new();
+    let mut pending = BTreeMap::new();
+    let mut printed_any = false;
+    let mut matched_files = 0;
+
+    print_header(config, out)?;
+
+    let mut searched_files = 0;
+
+    for found in receiver {
+        match found {
+            Found::Listed(dir, keys) => {
+                outstanding.extend(keys);
+                if let Some(dir) = dir {
+                    outstanding.remove(&dir);
+                }
+            }
+            Found::Searched(key, file, result) => {
+                pending.insert(key, (file, result));
+            }
+        }
+
+        while let Some((file, result)) = outstanding.first().and_then(|key| pending.remove(key)) {
+            outstanding.pop_first();
+            let (output, matched) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    progress.suspend(|| errors.report(&file, e));
+                    continue;
+                }
+            };
+            searched_files += 1;
+
+            if !output.is_empty() {
+                progress.suspend(|| write_output(config, out, &output, printed_any))?;
+                printed_any = true;
+            }
+            matched_files += usize::from(matched);
+
+            // With -q the first match settles the result, so skip the remaining files
+            if matched && config.quiet {
+                return Ok(true);
+            }
+        }
+    }
+
+    let elapsed = started.elapsed();
+    progress.suspend(|| print_footer(config, out, stats, elapsed, searched_files, matched_files))?;
+    Ok(matched_files > 0)
+}
+
+// Write the output of a file, separated from what came before it. Context groups from
+// different files are separated like groups within a file, files with headings by a
+// blank line and JSON objects by a comma. Every --vimgrep line, --json-lines event,
+// CSV row and --format line stands on its own.
+fn write_output<W: Write>(config: &Config, out: &mut W, output: &[u8], printed_any: bool) -> io::Result<()> {
+    let standalone = config.vimgrep || config.json_lines || config.csv || config.format.is_some();
+    if printed_any && !standalone {
+        if config.prints_json() {
+            write!(out, "{}", JSON_SEPARATOR)?;
+        } else if config.print_filenames && config.heading {
+            writeln!(out)?;
+        } else if let (true, Some(separator)) = (config.has_context(), &config.group_separator) {
+            writeln!(out, "{}", paint(config, &config.colors.separator, separator))?;
+        }
+    }
+    out.write_all(output)
+}
@@ -0,0 +1,532 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::iter;
+use std::ops::Range;
+use std::time::Duration;
+use serde_json::json;
+use crate::color::Style;
+use crate::config::Config;
+use crate::hyperlink::{file_url, wrap};
+use crate::matcher::Matcher;
+use crate::search::Match;
+use crate::stats::Stats;
+use crate::template::{Field, Template};
+use crate::STDIN_LABEL;
+
+// Printed between groups of context lines unless --group-separator gives another
+pub const GROUP_SEPARATOR: &str = "--";
+
+// With --json the objects for matching lines are printed one per line inside an array
+const JSON_DOCUMENT_START: &str = "[";
+const JSON_DOCUMENT_END: &str = "\n]";
+pub const JSON_SEPARATOR: &str = ",";
+
+// The first row printed with --csv
+const CSV_HEADER: &str = "path,line,column,text";
+
+// Marks where --max-columns cut a line short, at either end
+const ELLIPSIS: &str = "…";
+
+// Formats matches for the terminal, tracking where each group of context ends.
+// Output goes to any writer, so results can be buffered before reaching stdout.
+pub struct Printer<'a, W: Write> {
+    config: &'a Config,
+    matcher: &'a dyn Matcher,
+    stats: &'a Stats,
+    out: W,
+    // Line number of the last line printed for the current file
+    last_line_no: Option<usize>,
+    // Whether the current file's heading has been printed
+    printed_heading: bool,
+    // With --json-lines, whether the current file's `begin` event has been printed, and
+    // how many lines and matches it has had
+    began_file: bool,
+    matched_lines: usize,
+    matches: usize,
+    // Whether anything has been printed yet, across all files
+    printed_any: bool,
+}
+
+impl<'a, W: Write> Printer<'a, W> {
+    pub fn new(config: &'a Config, matcher: &'a dyn Matcher, stats: &'a Stats, out: W) -> Printer<'a, W> {
+        Printer {
+            config,
+            matcher,
+            stats,
+            out,
+            last_line_no: None,
+            printed_heading: false,
+            began_file: false,
+            matched_lines: 0,
+            matches: 0,
+            printed_any: false,
+        }
+    }
+
+    // The --stats counters that searches through this printer add to
+    pub fn stats(&self) -> &'a Stats {
+        self.stats
+    }
+
+    // Give back the writer, e.g. to collect the buffered output
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    // Reset the per-file state before printing matches from another file
+    pub fn begin_file(&mut self) {
+        self.last_line_no = None;
+        self.printed_heading = false;
+        self.began_file = false;
+        self.matched_lines = 0;
+        self.matches = 0;
+    }
+
+    // Finish printing the matches from a file, closing it with an `end` event for
+    // --json-lines if anything was printed for it
+    pub fn end_file(&mut self, file: &str) -> io::Result<()> {
+        if !self.config.prints_json_lines() || !self.began_file {
+            return Ok(());
+        }
+        let event = json!({
+            "type": "end",
+            "data": { "path": file, "matched_lines": self.matched_lines, "matches": self.matches },
+        });
+        writeln!(self.out, "{}", event)
+    }
+
+    pub fn print_filename(&mut self, file: &str) -> io::Result<()> {
+        write!(self.out, "{}{}", self.path(file, 1, 1), filename_terminator(self.config))
+    }
+
+    pub fn print_count(&mut self, file: &str, count: usize) -> io::Result<()> {
+        let file = self.path(file, 1, 1);
+        if self.config.print_filenames && self.config.null {
+            writeln!(self.out, "{}\0{}", file, count)
+        } else if self.config.print_filenames {
+            writeln!(self.out, "{}: {}", file, count)
+        } else {
+            writeln!(self.out, "{}", count)
+        }
+    }
+
+    pub fn print(&mut self, file: &str, m: &Match) -> io::Result<()> {
+        // Only the matched text is printed with -o, so context lines have nothing to show
+        if self.config.only_matching && m.is_context {
+            return Ok(());
+        }
+
+        if self.config.vimgrep {
+            return self.print_vimgrep(file, m);
+        }
+
+        if let Some(template) = &self.config.format {
+            return self.print_template(template, file, m);
+        }
+
+        if self.config.csv {
+            return self.print_csv(file, m);
+        }
+
+        if self.config.json_lines {
+            return self.print_json_event(file, m);
+        }
+
+        if self.config.json {
+            return self.print_json(file, m);
+        }
+
+        if self.uses_heading() && !self.printed_heading {
+            // Files are separated by a blank line rather than by the group separator
+            if self.printed_any {
+                writeln!(self.out)?;
+            }
+            writeln!(self.out, "{}", self.path(file, m.line_no, 1))?;
+            self.printed_heading = true;
+        } else if self.config.has_context() && self.printed_any {
+            // Separate groups of lines that are not adjacent to each other
+            let adjacent = self.last_line_no.is_some_and(|n| n + 1 == m.line_no);
+            if let (false, Some(separator)) = (adjacent, &self.config.group_separator) {
+                writeln!(self.out, "{}", paint(self.config, &self.config.colors.separator, separator))?;
+            }
+        }
+        self.last_line_no = Some(m.line_no);
+        self.printed_any = true;
+
+        if self.config.only_matching {
+            // Print each match on its own line, with -b giving the offset of the match itself
+            if let Some(replacement) = &self.config.replace {
+                for (start, _, replaced) in self.matcher.replace_iter(&m.line, replacement) {
+                    let visible = visible_range(&replaced, None, self.config.max_columns);
+                    let text = self.displayed(&replaced[visible.clone()], &mut 0);
+                    let elided = elision_end(&replaced, &visible);
+                    writeln!(self.out, "{}{}{}", self.prefix(file, m, Some(start)), text, elided)?;
+                }
+                return Ok(());
+            }
+            for (start, end) in self.matcher.find_iter(m.line.as_bytes()) {
+                let output = self.prefix(file, m, Some(start));
+                let matched = &m.line[start..end];
+                let visible = visible_range(matched, None, self.config.max_columns);
+                let text = self.displayed(&matched[visible.clone()], &mut 0);
+                let text = paint(self.config, &self.config.colors.matched, &text);
+                writeln!(self.out, "{}{}{}", output, text, elision_end(matched, &visible))?;
+            }
+            return Ok(());
+        }
+
+        // The text to print and where its matches are, which are only looked for when they
+        // are painted or a long line is centered on the first
+        let wants_matches = self.config.coloured_output || self.config.max_columns.is_some();
+        let (line, matches) = if let (Some(replacement), false) = (&self.config.replace, m.is_context) {
+            let mut replaced = String::with_capacity(m.line.len());
+            let mut ranges = Vec::new();
+            let mut last = 0;
+            for (start, end, text) in self.matcher.replace_iter(&m.line, replacement) {
+                replaced.push_str(&m.line[last..start]);
+                ranges.push((replaced.len(), replaced.len() + text.len()));
+                replaced.push_str(&text);
+                last = end;
+            }
+            replaced.push_str(&m.line[last..]);
+            (Cow::Owned(replaced), ranges)
+        } else if wants_matches {
+            (Cow::Borrowed(m.line.as_str()), self.matcher.find_iter(m.line.as_bytes()))
+        } else {
+            (Cow::Borrowed(m.line.as_str()), Vec::new())
+        };
+        // Paint every match, so lines without one (such as those selected by -v) stay
+        // uncolored. Rewritten text isn't painted.
+        let painted = self.config.coloured_output && (self.config.replace.is_none() || m.is_context);
+        let visible = visible_range(&line, matches.first().copied(), self.config.max_columns);
+
+        let mut output = self.prefix(file, m, None);
+        output.push_str(elision_start(&visible));
+        // Where the text printed so far ends on screen, for expanding tabs
+        let mut column = 0;
+        let mut last = visible.start;
+        let shown = matches.into_iter().filter(|&(start, end)| start < visible.end && end > visible.start);
+        for (start, end) in shown.filter(|_| painted) {
+            let (start, end) = (start.max(visible.start), end.min(visible.end));
+            output.push_str(&self.displayed(&line[last..start], &mut column));
+            output.push_str(&self.config.colors.matched.paint(&self.displayed(&line[start..end], &mut column)));
+            last = end;
+        }
+        output.push_str(&self.displayed(&line[last..visible.end], &mut column));
+        output.push_str(&elision_end(&line, &visible));
+        writeln!(self.out, "{}", output)
+    }
+
+    // Print a line for each match, giving the 1-based byte column it starts at. Context
+    // lines are left out, and lines selected without a match, such as with -v, are
+    // given column 1.
+    fn print_vimgrep(&mut self, file: &str, m: &Match) -> io::Result<()> {
+        if m.is_context {
+            return Ok(());
+        }
+        let mut starts: Vec<usize> = self.matcher.find_iter(m.line.as_bytes()).into_iter().map(|(start, _)| start).collect();
+        if starts.is_empty() {
+            starts.push(0);
+        }
+        for start in starts {
+            writeln!(self.out, "{}:{}:{}:{}", file, m.line_no, start + 1, self.printable(&m.line))?;
+        }
+        Ok(())
+    }
+
+    // Print a matching line as a JSON object on a line of its own. Context lines are left
+    // out. The caller prints the surrounding array.
+    fn print_json(&mut self, file: &str, m: &Match) -> io::Result<()> {
+        if m.is_context {
+            return Ok(());
+        }
+        let matches = self.matcher.find_iter(m.line.as_bytes());
+        let submatches: Vec<_> = matches
+            .iter()
+            .map(|&(start, end)| json!({ "match": &m.line[start..end], "start": start, "end": end }))
+            .collect();
+        let object = json!({
+            "path": file,
+            "line_number": m.line_no,
+            "column": matches.first().map_or(0, |&(start, _)| start) + 1,
+            "byte_offset": m.byte_offset,
+            "text": m.line,
+            "submatches": submatches,
+        });
+
+        if self.printed_any {
+            write!(self.out, "{}", JSON_SEPARATOR)?;
+        }
+        self.printed_any = true;
+        write!(self.out, "\n{}", object)
+    }
+
+    // Print a `match` or `context` event for the line, after a `begin` event for the
+    // first line of a file
+    fn print_json_event(&mut self, file: &str, m: &Match) -> io::Result<()> {
+        if !self.began_file {
+            writeln!(self.out, "{}", json!({ "type": "begin", "data": { "path": file } }))?;
+            self.began_file = true;
+        }
+        let matches = if m.is_context { Vec::new() } else { self.matcher.find_iter(m.line.as_bytes()) };
+        if !m.is_context {
+            self.matched_lines += 1;
+            self.matches += matches.len();
+        }
+        let submatches: Vec<_> = matches
+            .iter()
+            .map(|&(start, end)| json!({ "match": &m.line[start..end], "start": start, "end": end }))
+            .collect();
+        let event = json!({
+            "type": if m.is_context { "context" } else { "match" },
+            "data": {
+                "path": file,
+                "line_number": m.line_no,
+                "byte_offset": m.byte_offset,
+                "text": m.line,
+                "submatches": submatches,
+            },
+        });
+        writeln!(self.out, "{}", event)
+    }
+
+    // Print a matching line as a CSV row. Context lines are left out.
+    fn print_csv(&mut self, file: &str, m: &Match) -> io::Result<()> {
+        if m.is_context {
+            return Ok(());
+        }
+        let column = self.matcher.find(m.line.as_bytes()).map_or(0, |(start, _)| start) + 1;
+        writeln!(self.out, "{},{},{},{}", csv_field(file), m.line_no, column, csv_field(&m.line))
+    }
+
+    // Print a matching line in the layout given with --format. Context lines are left out.
+    fn print_template(&mut self, template: &Template, file: &str, m: &Match) -> io::Result<()> {
+        if m.is_context {
+            return Ok(());
+        }
+        let first = self.matcher.find(m.line.as_bytes());
+        let output = template.render(|field| match field {
+            Field::Path => file.to_string(),
+            Field::Line => m.line_no.to_string(),
+            Field::Column => (first.map_or(0, |(start, _)| start) + 1).to_string(),
+            Field::Offset => m.byte_offset.to_string(),
+            Field::Text => self.printable(&m.line).into_owned(),
+            Field::Match => first.map_or_else(String::new, |(start, end)| self.printable(&m.line[start..end]).into_owned()),
+        });
+        writeln!(self.out, "{}", output)
+    }
+
+    // Paint a file name, making it a link to the line and column with --hyperlink
+    fn path<'b>(&self, file: &'b str, line_no: usize, column: usize) -> Cow<'b, str> {
+        let painted = paint(self.config, &self.config.colors.path, file);
+        let url = match &self.config.hyperlink {
+            Some(format) if file != STDIN_LABEL => file_url(format, file, line_no, column),
+            _ => None,
+        };
+        match url {
+            Some(url) => Cow::Owned(wrap(&url, &painted)),
+            None => painted,
+        }
+    }
+
+    fn uses_heading(&self) -> bool {
+        self.config.print_filenames && self.config.heading
+    }
+
+    // With -a, binary files can contain control characters that would garble the
+    // terminal, so they are shown as `.` (keeping offsets within the line intact)
+    fn printable<'b>(&self, text: &'b str) -> Cow<'b, str> {
+        if self.config.text && text.chars().any(is_unprintable) {
+            Cow::Owned(text.chars().map(|c| if is_unprintable(c) { '.' } else { c }).collect())
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    // Part of a line as it is printed: made printable, and with --expand-tabs with its
+    // tabs turned into spaces. Matches are found in the line as it was read, so the text is
+    // expanded a part at a time, with `column` tracking where on screen each part starts.
+    // Tab stops are counted from the start of the line, after the prefix.
+    fn displayed<'b>(&self, text: &'b str, column: &mut usize) -> Cow<'b, str> {
+        let text = self.printable(text);
+        let Some(width) = self.config.tab_width else {
+            return text;
+        };
+        let mut expanded = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '\t' => {
+                    let spaces = width - *column % width;
+                    expanded.extend(iter::repeat_n(' ', spaces));
+                    *column += spaces;
+                }
+                // A line of a multiline match starts over at the left
+                '\n' => {
+                    expanded.push(c);
+                    *column = 0;
+                }
+                _ => {
+                    expanded.push(c);
+                    *column += 1;
+                }
+            }
+        }
+        Cow::Owned(expanded)
+    }
+
+    // Build the `file: line: column: offset: ` prefix from whichever fields are enabled.
+    // With -o `match_start` is where the printed match starts in the line, which the
+    // column and byte offset refer to rather than the first match and the line.
+    fn prefix(&self, file: &str, m: &Match, match_start: Option<usize>) -> String {
+        // Context lines use `-` after the prefix instead of `:`, like GNU grep
+        let delimiter = if m.is_context { "- " } else { ": " };
+
+        let colors = &self.config.colors;
+        let delimiter = paint(self.config, &colors.separator, delimiter);
+
+        // The column of the match, for the link and --column
+        let column = || match_start.or_else(|| self.matcher.find(m.line.as_bytes()).map(|(start, _)| start)).unwrap_or(0) + 1;
+
+        let mut output = String::new();
+        if self.config.print_filenames && !self.uses_heading() {
+            let column = if m.is_context { 1 } else { column() };
+            output.push_str(&self.path(file, m.line_no, column));
+            // With -Z a NUL byte marks the end of the name, which may itself contain `: `
+            output.push_str(if self.config.null { "\0" } else { &delimiter });
+        }
+        if self.config.print_line_no {
+            output.push_str(&paint(self.config, &colors.line, &m.line_no.to_string()));
+            output.push_str(&delimiter);
+        }
+        // Context lines have no match to give the column of
+        if self.config.column && !m.is_context {
+            output.push_str(&paint(self.config, &colors.column, &column().to_string()));
+            output.push_str(&delimiter);
+        }
+        if self.config.print_byte_offset {
+            output.push_str(&(m.byte_offset + match_start.unwrap_or(0)).to_string());
+            output.push_str(&delimiter);
+        }
+        output
+    }
+}
+
+// Paint part of the output in its --colors style, if colors are enabled
+pub fn paint<'b>(config: &Config, style: &Style, text: &'b str) -> Cow<'b, str> {
+    if config.coloured_output {
+        Cow::Owned(style.paint(text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+// Names listed on their own end with a newline, or with --null a NUL byte
+pub fn filename_terminator(config: &Config) -> char {
+    if config.null {
+        '\0'
+    } else {
+        '\n'
+    }
+}
+
+// With a --max-columns limit, the byte range of the text that is printed. A long line is
+// shown from its start, or if it has a match, from far enough before the first match to
+// center it, so that it can be seen.
+fn visible_range(text: &str, first_match: Option<(usize, usize)>, max_columns: Option<usize>) -> Range<usize> {
+    let Some(max) = max_columns else {
+        return 0..text.len();
+    };
+    let len = text.chars().count();
+    if len <= max {
+        return 0..text.len();
+    }
+    let start = match first_match {
+        Some((start, end)) => {
+            let before = text[..start].chars().count();
+            // Half the room the match leaves goes before it, unless the line ends first
+            let lead = max.saturating_sub(text[start..end].chars().count()) / 2;
+            before.saturating_sub(lead).min(len - max)
+        }
+        None => 0,
+    };
+    let byte = |chars: usize| text.char_indices().nth(chars).map_or(text.len(), |(i, _)| i);
+    byte(start)..byte(start + max)
+}
+
+// Marks a line shown from after its start by --max-columns
+fn elision_start(visible: &Range<usize>) -> &'static str {
+    if visible.start > 0 {
+        ELLIPSIS
+    } else {
+        ""
+    }
+}
+
+// Follows a line cut short by --max-columns, giving how many bytes of it weren't shown
+fn elision_end(text: &str, visible: &Range<usize>) -> String {
+    let omitted = text.len() - visible.len();
+    let end = if visible.end < text.len() { ELLIPSIS } else { "" };
+    match omitted {
+        0 => String::new(),
+        1 => format!("{} [1 byte omitted]", end),
+        _ => format!("{} [{} bytes omitted]", end, omitted),
+    }
+}
+
+// Quote a CSV field if it contains a delimiter, quote or line break, doubling any quotes
+fn csv_field(text: &str) -> Cow<'_, str> {
+    if text.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+// Print what comes before the results: the opening of a --json document or the --csv
+// header row
+pub fn print_header<W: Write>(config: &Config, out: &mut W) -> io::Result<()> {
+    if config.prints_json() {
+        write!(out, "{}", JSON_DOCUMENT_START)?;
+    }
+    if config.prints_csv() {
+        writeln!(out, "{}", CSV_HEADER)?;
+    }
+    Ok(())
+}
+
+// Print what comes after the results: the end of a --json document, the `summary` event
+// of --json-lines, and the statistics. With --stats-json these go to stderr as a JSON
+// object, unless they can be part of the summary event.
+pub fn print_footer<W: Write>(
+    config: &Config,
+    out: &mut W,
+    stats: &Stats,
+    elapsed: Duration,
+    searched_files: usize,
+    matched_files: usize,
+) -> io::Result<()> {
+    if config.prints_json() {
+        writeln!(out, "{}", JSON_DOCUMENT_END)?;
+    }
+    if config.prints_json_lines() {
+        let mut summary = json!({
+            "elapsed_secs": elapsed.as_secs_f64(),
+            "searched_files": searched_files,
+            "matched_files": matched_files,
+        });
+        if config.stats_json {
+            summary["stats"] = stats.to_json(elapsed);
+        }
+        writeln!(out, "{}", json!({ "type": "summary", "data": summary }))?;
+    } else if config.stats_json {
+        eprintln!("{}", stats.to_json(elapsed));
+    }
+    if config.stats {
+        stats.print(out, elapsed)?;
+    }
+    Ok(())
+}
+
+fn is_unprintable(c: char) -> bool {
+    c.is_control() && c != '\t'
+}
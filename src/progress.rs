@@ -0,0 +1,102 @@
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Searches that finish sooner than this never show any progress
+const PROGRESS_DELAY: Duration = Duration::from_millis(500);
+
+// How often the progress line is redrawn
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// Longest part of the current path shown, keeping its end
+const MAX_PATH_LEN: usize = 60;
+
+// Erases the current terminal line
+const CLEAR_LINE: &str = "\r\x1b[K";
+
+// A progress line on stderr for long recursive searches, giving how many of the files
+// found so far have been searched and the file being searched. It is erased whenever
+// results are printed, and only shown when stderr is a terminal.
+pub struct Progress {
+    enabled: bool,
+    started: Instant,
+    done: AtomicUsize,
+    current: Mutex<String>,
+    // Whether the line is on screen. Held while results are written so it isn't redrawn
+    // in the middle of them.
+    shown: Mutex<bool>,
+}
+
+impl Progress {
+    pub fn new(enabled: bool) -> Progress {
+        Progress {
+            enabled: enabled && io::stderr().is_terminal(),
+            started: Instant::now(),
+            done: AtomicUsize::new(0),
+            current: Mutex::new(String::new()),
+            shown: Mutex::new(false),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn start_file(&self, file: &Path) {
+        if self.enabled {
+            let mut current = self.current.lock().unwrap();
+            current.clear();
+            current.push_str(&file.to_string_lossy());
+        }
+    }
+
+    pub fn finish_file(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Redraw the line, once the search has run long enough to need it
+    pub fn draw(&self, discovered: usize) {
+        if !self.enabled || self.started.elapsed() < PROGRESS_DELAY {
+            return;
+        }
+        let current = self.current.lock().unwrap().clone();
+        let mut shown = self.shown.lock().unwrap();
+        let mut stderr = io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "{}{}/{} files  {}",
+            CLEAR_LINE,
+            self.done.load(Ordering::Relaxed),
+            discovered,
+            shorten(&current)
+        );
+        let _ = stderr.flush();
+        *shown = true;
+    }
+
+    // Run `print` with the line erased, so results never end up on the same line
+    pub fn suspend<T>(&self, print: impl FnOnce() -> T) -> T {
+        let mut shown = self.shown.lock().unwrap();
+        if *shown {
+            let _ = write!(io::stderr(), "{}", CLEAR_LINE);
+            *shown = false;
+        }
+        print()
+    }
+
+    pub fn clear(&self) {
+        self.suspend(|| ());
+    }
+}
+
+// Keep the end of a long path, which names the file
+fn shorten(path: &str) -> String {
+    let len = path.chars().count();
+    if len <= MAX_PATH_LEN {
+        return path.to_string();
+    }
+    let tail: String = path.chars().skip(len - MAX_PATH_LEN + 3).collect();
+    format!("...{}", tail)
+}
@@ -0,0 +1,198 @@
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::slice;
+use clap::{CommandFactory, Parser};
+use glob::Pattern;
+use crate::config::suggest_flag;
+use crate::files::{is_binary, parse_filenames, FileErrors};
+use crate::filter::FileFilter;
+use crate::matcher::{MatchOptions, Matcher, PatternMatcher};
+
+pub const REPLACE_COMMAND: &str = "replace";
+
+const BACKUP_EXTENSION: &str = ".bak";
+
+// Added to the name of a file while its new contents are written next to it
+const TEMP_EXTENSION: &str = ".replace-tmp";
+
+pub struct ReplaceConfig {
+    pub pattern: String,
+    pub replacement: String,
+    pub filenames: Vec<PathBuf>,
+    pub is_case_insensitive: bool,
+    pub fixed_strings: bool,
+    pub recursive_search: bool,
+    pub dry_run: bool,
+    pub backup: bool,
+}
+
+// The arguments following the `replace` subcommand, as clap sees them
+#[derive(Parser)]
+#[command(
+    name = "grep replace",
+    bin_name = "grep replace",
+    about = "Rewrites matching lines in place, where $1 or ${name} in <REPLACEMENT> refer to capture groups"
+)]
+struct ReplaceArgs {
+    #[arg(short, long, help = "Case-insensitive search")]
+    ignore_case: bool,
+    #[arg(short = 'F', long, help = "Treat the pattern as a fixed string rather than a regular expression")]
+    fixed_strings: bool,
+    #[arg(short, long, help = "Recursive directory search")]
+    recursive: bool,
+    #[arg(long, help = "Report what would change without writing any files")]
+    dry_run: bool,
+    #[arg(long, help = "Don't keep a copy of each changed file as <file>.bak")]
+    no_backup: bool,
+    #[arg(help = "The regular expression to look for")]
+    pattern: String,
+    #[arg(help = "What each match is replaced with")]
+    replacement: String,
+    #[arg(required = true, help = "The files to rewrite, or directories with -r")]
+    files: Vec<PathBuf>,
+}
+
+// The command line interface of the subcommand, for generating shell completions
+pub(crate) fn command() -> clap::Command {
+    ReplaceArgs::command()
+}
+
+impl ReplaceConfig {
+    // Parse the arguments following the `replace` subcommand, starting with its name
+    pub fn new<I, T>(args: I) -> Result<ReplaceConfig, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = ReplaceArgs::try_parse_from(args).map_err(|e| suggest_flag(e, &ReplaceArgs::command()))?;
+        Ok(ReplaceConfig {
+            pattern: args.pattern,
+            replacement: args.replacement,
+            filenames: args.files,
+            is_case_insensitive: args.ignore_case,
+            fixed_strings: args.fixed_strings,
+            recursive_search: args.recursive,
+            dry_run: args.dry_run,
+            backup: !args.no_backup,
+        })
+    }
+}
+
+// Rewrite the matching lines of each file, returning whether anything was changed. A file
+// that can't be read or written is reported and left as it is.
+pub fn run_replace(config: ReplaceConfig) -> Result<bool, Box<dyn Error>> {
+    let options = MatchOptions {
+        case_insensitive: config.is_case_insensitive,
+        fixed_strings: config.fixed_strings,
+        ..MatchOptions::default()
+    };
+    let matcher = PatternMatcher::new(slice::from_ref(&config.pattern), &options)?;
+    let errors = FileErrors::default();
+    // Backups left by an earlier run are never rewritten themselves
+    let filter = FileFilter::excluding(vec![Pattern::new(&format!("*{}", BACKUP_EXTENSION))?]);
+    let files = parse_filenames(&config.filenames, config.recursive_search, &filter, &errors);
+
+    let mut changed_files = 0;
+    let mut changed_lines = 0;
+    let verb = if config.dry_run { "would be changed" } else { "changed" };
+
+    for file in files {
+        let bytes = match fs::read(&file) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                errors.report(&file, e);
+                continue;
+            }
+        };
+        let contents = match String::from_utf8(bytes) {
+            Ok(contents) if !is_binary(contents.as_bytes()) => contents,
+            _ => {
+                eprintln!("{}: binary file, not modified", file.display());
+                continue;
+            }
+        };
+
+        let (replaced, lines) = replace_lines(&matcher, &contents, &config.replacement);
+        if lines == 0 {
+            continue;
+        }
+
+        if !config.dry_run {
+            let backup = if config.backup { write_backup(&file, &contents) } else { Ok(()) };
+            // The file is only rewritten once its backup is safe
+            if let Err(e) = backup.and_then(|_| rewrite(&file, &replaced)) {
+                errors.report(&file, e);
+                continue;
+            }
+        }
+
+        println!("{}: {} lines {}", file.display(), lines, verb);
+        changed_files += 1;
+        changed_lines += lines;
+    }
+
+    println!("{} lines {} in {} files", changed_lines, verb, changed_files);
+
+    errors.into_result(changed_lines > 0)
+}
+
+// Keep a copy of the original contents as <file>.bak, or as <file>.1.bak and so on if
+// earlier backups exist, so a backup is never overwritten
+fn write_backup(file: &Path, contents: &str) -> io::Result<()> {
+    for n in 0usize.. {
+        let mut backup = OsString::from(file);
+        if n > 0 {
+            backup.push(format!(".{}", n));
+        }
+        backup.push(BACKUP_EXTENSION);
+        match File::create_new(&backup) {
+            Ok(mut handle) => return handle.write_all(contents.as_bytes()),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+// Write the new contents next to the file and rename them over it, so a failed write
+// leaves the file as it was. The file keeps its permissions, and a symlink is followed
+// so the file it points to is rewritten rather than the link replaced.
+fn rewrite(file: &Path, contents: &str) -> io::Result<()> {
+    let file = fs::canonicalize(file)?;
+    let mut temp = OsString::from(&file);
+    temp.push(TEMP_EXTENSION);
+    let temp = PathBuf::from(temp);
+    let permissions = fs::metadata(&file)?.permissions();
+    let mut handle = File::create_new(&temp)?;
+    let written = handle.write_all(contents.as_bytes()).and_then(|_| handle.set_permissions(permissions));
+    drop(handle);
+    if let Err(e) = written.and_then(|_| fs::rename(&temp, &file)) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Replace the matches on every line, keeping the original line endings.
+// Returns the new contents and how many lines were changed.
+fn replace_lines(matcher: &dyn Matcher, contents: &str, replacement: &str) -> (String, usize) {
+    let mut output = String::with_capacity(contents.len());
+    let mut changed = 0;
+
+    for line in contents.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[text.len()..];
+
+        let replaced = matcher.replace_all(text, replacement);
+        if replaced != text {
+            changed += 1;
+        }
+        output.push_str(&replaced);
+        output.push_str(ending);
+    }
+
+    (output, changed)
+}
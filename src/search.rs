@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use crate::config::Config;
+use crate::matcher::Matcher;
+
+// A line selected by the search, or a context line printed around one
+pub struct Match {
+    pub line_no: usize,
+    // Offset of the start of the line from the start of the input
+    pub byte_offset: usize,
+    pub line: String,
+    pub is_context: bool,
+}
+
+// Iterates over the lines of a reader without their terminators, along with
+// the byte offset each line starts at
+struct Lines<R> {
+    reader: R,
+    offset: usize,
+}
+
+impl<R: BufRead> Lines<R> {
+    fn new(reader: R) -> Lines<R> {
+        Lines { reader, offset: 0 }
+    }
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = io::Result<(usize, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = Vec::new();
+        match self.reader.read_until(b'\n', &mut bytes) {
+            Ok(0) => None,
+            Ok(len) => {
+                let offset = self.offset;
+                self.offset += len;
+                // Strip `\n` or `\r\n`, like `BufRead::lines`
+                if bytes.ends_with(b"\n") {
+                    bytes.pop();
+                    if bytes.ends_with(b"\r") {
+                        bytes.pop();
+                    }
+                }
+                Some(Ok((offset, decode(bytes))))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+// Convert bytes read from the input into a string. Invalid UTF-8 is replaced with U+FFFD
+// rather than failing, so a stray bad byte in a text file doesn't abort the search.
+pub fn decode(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+// Search the contents line by line and return the selected lines with their context
+pub fn search(config: &Config, matcher: &dyn Matcher, contents: &str) -> Vec<Match> {
+    let mut matches = Vec::new();
+    // Reading from a byte slice cannot fail
+    search_reader(config, matcher, contents.as_bytes(), |m| {
+        matches.push(m);
+        Ok(())
+    })
+    .unwrap();
+    matches
+}
+
+// Search a reader line by line, handing each selected line to `sink` as soon as it is read.
+// An error returned by the sink stops the search.
+pub fn search_reader<R: BufRead, F: FnMut(Match) -> io::Result<()>>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    reader: R,
+    mut sink: F,
+) -> io::Result<()> {
+    // The last `before_context` lines that were not printed, oldest first
+    let mut before = VecDeque::with_capacity(config.before_context);
+    // Trailing context lines still owed to the last match
+    let mut after_remaining = 0;
+    let mut match_count = 0;
+
+    for (line_no, line) in (1..).zip(Lines::new(reader)) {
+        let (byte_offset, line) = line?;
+
+        // Once -m is reached only the trailing context of the last match is left to print
+        let limit_reached = config.reached_max_count(match_count);
+        if limit_reached && after_remaining == 0 {
+            break;
+        }
+
+        let matched = !limit_reached && matcher.is_match(line.as_bytes()) != config.invert_match;
+
+        if matched {
+            for m in before.drain(..) {
+                sink(m)?;
+            }
+            sink(Match { line_no, byte_offset, line, is_context: false })?;
+            after_remaining = config.after_context;
+            match_count += 1;
+        } else if config.passthru {
+            sink(Match { line_no, byte_offset, line, is_context: true })?;
+        } else if after_remaining > 0 {
+            sink(Match { line_no, byte_offset, line, is_context: true })?;
+            after_remaining -= 1;
+        } else if config.before_context > 0 {
+            if before.len() == config.before_context {
+                before.pop_front();
+            }
+            before.push_back(Match { line_no, byte_offset, line, is_context: true });
+        }
+    }
+
+    Ok(())
+}
+
+// Match the whole buffer at once so patterns can span lines. Each match is reported as
+// the full lines it touches, numbered by the line it starts on, and matches that share
+// a line are merged into one result. As when searching line by line, the `\r` of a last
+// line ending with `\r\n` is left out.
+pub fn search_multiline(config: &Config, matcher: &dyn Matcher, contents: &str) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    let mut line_no = 1;
+    // `line_no` is the number of the line starting at `counted_to`
+    let mut counted_to = 0;
+    // End of the lines covered by the last result
+    let mut covered_to = 0;
+
+    for (start, end) in matcher.find_iter(contents.as_bytes()) {
+        if config.reached_max_count(matches.len()) {
+            break;
+        }
+
+        let block_start = contents[..start].rfind('\n').map_or(0, |i| i + 1);
+        // A match ending with a newline doesn't pull in the following line
+        let last = if contents[..end].ends_with('\n') { end - 1 } else { end };
+        let mut block_end = contents[last..].find('\n').map_or(contents.len(), |i| last + i);
+        if contents[..block_end].ends_with('\r') {
+            block_end -= 1;
+        }
+
+        if let Some(previous) = matches.last_mut().filter(|_| block_start < covered_to) {
+            if block_end > covered_to {
+                previous.line.push_str(&contents[covered_to..block_end]);
+                covered_to = block_end;
+            }
+            continue;
+        }
+
+        line_no += contents[counted_to..block_start].matches('\n').count();
+        counted_to = block_start;
+        covered_to = block_end;
+        matches.push(Match {
+            line_no,
+            byte_offset: block_start,
+            line: contents[block_start..block_end].to_string(),
+            is_context: false,
+        });
+    }
+
+    matches
+}
+
+// Count the selected lines without building any matches or context
+pub fn count_matches<R: BufRead>(config: &Config, matcher: &dyn Matcher, reader: R) -> io::Result<usize> {
+    let mut count = 0;
+    for line in Lines::new(reader) {
+        if config.reached_max_count(count) {
+            break;
+        }
+        if matcher.is_match(line?.1.as_bytes()) != config.invert_match {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+// Check whether any line is selected, stopping at the first one
+pub fn has_match<R: BufRead>(config: &Config, matcher: &dyn Matcher, reader: R) -> io::Result<bool> {
+    if config.reached_max_count(0) {
+        return Ok(false);
+    }
+    for line in Lines::new(reader) {
+        if matcher.is_match(line?.1.as_bytes()) != config.invert_match {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Iterates over the selected lines of a reader, reading no further than it is advanced.
+// Context lines aren't included.
+pub struct SelectedLines<'m, R> {
+    lines: Lines<R>,
+    matcher: &'m dyn Matcher,
+    line_no: usize,
+    count: usize,
+    invert_match: bool,
+    max_count: Option<usize>,
+}
+
+impl<'m, R: BufRead> SelectedLines<'m, R> {
+    pub fn new(config: &Config, matcher: &'m dyn Matcher, reader: R) -> SelectedLines<'m, R> {
+        SelectedLines {
+            lines: Lines::new(reader),
+            matcher,
+            line_no: 0,
+            count: 0,
+            invert_match: config.invert_match,
+            max_count: config.max_count,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for SelectedLines<'_, R> {
+    type Item = io::Result<Match>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.max_count.is_none_or(|max| self.count < max) {
+            let (byte_offset, line) = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            self.line_no += 1;
+            if self.matcher.is_match(line.as_bytes()) != self.invert_match {
+                self.count += 1;
+                return Some(Ok(Match { line_no: self.line_no, byte_offset, line, is_context: false }));
+            }
+        }
+        None
+    }
+}
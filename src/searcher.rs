@@ -0,0 +1,257 @@
+// Searching from code rather than the command line: a Searcher is set up with a builder,
+// which starts from the defaults and checks that the options go together
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::config::Config;
+use crate::encoding::{detect_decoder, DecodeReader};
+use crate::files::is_binary;
+use crate::matcher::Matcher;
+use crate::printer::{Printer, GROUP_SEPARATOR};
+use crate::search::{decode, search, search_multiline, search_reader, Match, SelectedLines};
+use crate::search_input;
+use crate::stats::Stats;
+
+// Searches text, readers or files with a matcher, e.g.
+// `Searcher::builder().line_numbers(true).context(2, 2).build()?`
+pub struct Searcher {
+    config: Config,
+}
+
+// A selected line of a file, as returned by `search_file`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileMatch {
+    pub path: PathBuf,
+    pub line_no: usize,
+    // Bytes from the start of the file to the first match on the line, or to the whole
+    // line when it was selected by inverting the match
+    pub span: Range<usize>,
+    pub line: String,
+}
+
+// Collects the options of a Searcher. Options that aren't set keep their defaults, which
+// match those of the command line.
+pub struct SearcherBuilder {
+    config: Config,
+}
+
+impl Searcher {
+    pub fn builder() -> SearcherBuilder {
+        SearcherBuilder::new()
+    }
+
+    // The options the searcher was built with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // Return the selected lines of the text with their context
+    pub fn search_str(&self, matcher: &dyn Matcher, text: &str) -> Vec<Match> {
+        if self.config.multiline {
+            search_multiline(&self.config, matcher, text)
+        } else {
+            search(&self.config, matcher, text)
+        }
+    }
+
+    // Hand each selected line of the reader to `sink` as soon as it is read, or with
+    // multiline matching once the whole input has been read. An error returned by the
+    // sink stops the search.
+    pub fn search_reader<R: BufRead, F: FnMut(Match) -> io::Result<()>>(
+        &self,
+        matcher: &dyn Matcher,
+        mut reader: R,
+        mut sink: F,
+    ) -> io::Result<()> {
+        if !self.config.multiline {
+            return search_reader(&self.config, matcher, reader, sink);
+        }
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        for m in search_multiline(&self.config, matcher, &decode(bytes)) {
+            sink(m)?;
+        }
+        Ok(())
+    }
+
+    // Return the selected lines of a file, without context. The file is read as the
+    // iterator is advanced, or in full with multiline matching. Binary files yield
+    // nothing unless `text` is set.
+    pub fn search_file<'m>(
+        &self,
+        path: &Path,
+        matcher: &'m dyn Matcher,
+    ) -> io::Result<impl Iterator<Item = io::Result<FileMatch>> + 'm> {
+        let config = &self.config;
+        let mut reader: Box<dyn BufRead> = Box::new(BufReader::new(File::open(path)?));
+        if let Some(decoder) = detect_decoder(config.encoding, &mut reader)? {
+            reader = Box::new(BufReader::new(DecodeReader::new(reader, decoder)));
+        } else if !config.text && is_binary(reader.fill_buf()?) {
+            reader = Box::new(io::empty());
+        }
+
+        let matches: Box<dyn Iterator<Item = io::Result<Match>>> = if config.multiline {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            Box::new(search_multiline(config, matcher, &decode(bytes)).into_iter().map(Ok))
+        } else {
+            Box::new(SelectedLines::new(config, matcher, reader))
+        };
+        let path = path.to_path_buf();
+        Ok(matches.map(move |m| m.map(|m| FileMatch::new(&path, matcher, m))))
+    }
+
+    // Search a file, or stdin for `-`, printing the results to `out` as the command line
+    // would. Returns whether anything was selected.
+    pub fn search_path<W: Write>(&self, matcher: &dyn Matcher, path: &Path, out: W) -> io::Result<bool> {
+        // As for a search from the command line, the colored crate's own guess about the
+        // terminal is overridden
+        if self.config.coloured_output {
+            colored::control::set_override(true);
+        }
+        let stats = Stats::new(false);
+        let mut printer = Printer::new(&self.config, matcher, &stats, out);
+        let matched = search_input(&self.config, matcher, &mut printer, path)?;
+        printer.into_inner().flush()?;
+        Ok(matched)
+    }
+}
+
+impl Default for Searcher {
+    fn default() -> Searcher {
+        Searcher { config: SearcherBuilder::new().config }
+    }
+}
+
+impl FileMatch {
+    fn new(path: &Path, matcher: &dyn Matcher, m: Match) -> FileMatch {
+        let (start, end) = matcher.find(m.line.as_bytes()).unwrap_or((0, m.line.len()));
+        FileMatch {
+            path: path.to_path_buf(),
+            line_no: m.line_no,
+            span: m.byte_offset + start..m.byte_offset + end,
+            line: m.line,
+        }
+    }
+}
+
+// Return the selected lines of a file with the default options, so a search can be
+// embedded without setting up a Searcher
+pub fn search_file<'m>(
+    path: &Path,
+    matcher: &'m dyn Matcher,
+) -> io::Result<impl Iterator<Item = io::Result<FileMatch>> + 'm> {
+    Searcher::default().search_file(path, matcher)
+}
+
+impl SearcherBuilder {
+    pub fn new() -> SearcherBuilder {
+        let config = Config {
+            group_separator: Some(GROUP_SEPARATOR.to_string()),
+            ..Config::default()
+        };
+        SearcherBuilder { config }
+    }
+
+    // Check the options and build the searcher
+    pub fn build(self) -> Result<Searcher, Box<dyn Error>> {
+        let config = self.config;
+        if config.multiline && config.invert_match {
+            return Err("multiline matching can't be inverted".into());
+        }
+        if config.multiline && (config.has_context() || config.passthru) {
+            return Err("multiline matching doesn't print context lines".into());
+        }
+        if config.max_columns == Some(0) {
+            return Err("max columns must be at least 1".into());
+        }
+        Ok(Searcher { config })
+    }
+
+    // Print the line number of each line
+    pub fn line_numbers(mut self, yes: bool) -> SearcherBuilder {
+        self.config.print_line_no = yes;
+        self
+    }
+
+    // Print the path of the file before each line
+    pub fn file_names(mut self, yes: bool) -> SearcherBuilder {
+        self.config.print_filenames = yes;
+        self
+    }
+
+    // Print the column of the first match on each line
+    pub fn column(mut self, yes: bool) -> SearcherBuilder {
+        self.config.column = yes;
+        self
+    }
+
+    // Print the byte offset of each line, or of each match with only_matching
+    pub fn byte_offset(mut self, yes: bool) -> SearcherBuilder {
+        self.config.print_byte_offset = yes;
+        self
+    }
+
+    // Print only the matched parts of each line
+    pub fn only_matching(mut self, yes: bool) -> SearcherBuilder {
+        self.config.only_matching = yes;
+        self
+    }
+
+    // Select the lines that don't match
+    pub fn invert(mut self, yes: bool) -> SearcherBuilder {
+        self.config.invert_match = yes;
+        self
+    }
+
+    // Include this many lines of context before and after each selected line
+    pub fn context(mut self, before: usize, after: usize) -> SearcherBuilder {
+        self.config.before_context = before;
+        self.config.after_context = after;
+        self
+    }
+
+    // Include every line that isn't selected as context
+    pub fn passthru(mut self, yes: bool) -> SearcherBuilder {
+        self.config.passthru = yes;
+        self
+    }
+
+    // Stop searching an input after this many selected lines
+    pub fn max_count(mut self, max: Option<usize>) -> SearcherBuilder {
+        self.config.max_count = max;
+        self
+    }
+
+    // Match patterns against the whole input, so they can span lines
+    pub fn multiline(mut self, yes: bool) -> SearcherBuilder {
+        self.config.multiline = yes;
+        self
+    }
+
+    // Search binary files as text
+    pub fn text(mut self, yes: bool) -> SearcherBuilder {
+        self.config.text = yes;
+        self
+    }
+
+    // Color printed matches, paths and line numbers
+    pub fn color(mut self, yes: bool) -> SearcherBuilder {
+        self.config.coloured_output = yes;
+        self
+    }
+
+    // Cut printed lines down to this many characters around their first match
+    pub fn max_columns(mut self, max: Option<usize>) -> SearcherBuilder {
+        self.config.max_columns = max;
+        self
+    }
+}
+
+impl Default for SearcherBuilder {
+    fn default() -> SearcherBuilder {
+        SearcherBuilder::new()
+    }
+}
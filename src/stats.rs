@@ -0,0 +1,123 @@
+use std::io::{self, BufRead, Read, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use serde_json::{json, Value};
+
+// Counters for --stats and --stats-json, shared by every thread of a search.
+// Nothing is counted unless they are enabled.
+#[derive(Default)]
+pub struct Stats {
+    enabled: bool,
+    searched_files: AtomicUsize,
+    skipped_files: AtomicUsize,
+    matched_files: AtomicUsize,
+    lines: AtomicU64,
+    matched_lines: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl Stats {
+    pub fn new(enabled: bool) -> Stats {
+        Stats { enabled, ..Stats::default() }
+    }
+
+    // Record that an input was searched, with how many of its lines matched
+    pub fn add_searched(&self, matched_lines: usize) {
+        if self.enabled {
+            self.searched_files.fetch_add(1, Ordering::Relaxed);
+            self.matched_lines.fetch_add(matched_lines as u64, Ordering::Relaxed);
+            if matched_lines > 0 {
+                self.matched_files.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Record an input that was left unsearched, such as a binary file
+    pub fn add_skipped(&self) {
+        if self.enabled {
+            self.skipped_files.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn add_read(&self, bytes: usize, lines: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.lines.fetch_add(lines as u64, Ordering::Relaxed);
+    }
+
+    pub fn to_json(&self, elapsed: Duration) -> Value {
+        json!({
+            "searched_files": self.searched_files.load(Ordering::Relaxed),
+            "skipped_files": self.skipped_files.load(Ordering::Relaxed),
+            "matched_files": self.matched_files.load(Ordering::Relaxed),
+            "lines": self.lines.load(Ordering::Relaxed),
+            "matched_lines": self.matched_lines.load(Ordering::Relaxed),
+            "bytes": self.bytes.load(Ordering::Relaxed),
+            "elapsed_secs": elapsed.as_secs_f64(),
+        })
+    }
+
+    pub fn print<W: Write>(&self, out: &mut W, elapsed: Duration) -> io::Result<()> {
+        writeln!(out)?;
+        writeln!(out, "{} files searched", self.searched_files.load(Ordering::Relaxed))?;
+        writeln!(out, "{} files skipped", self.skipped_files.load(Ordering::Relaxed))?;
+        writeln!(out, "{} files contained matches", self.matched_files.load(Ordering::Relaxed))?;
+        writeln!(out, "{} lines searched", self.lines.load(Ordering::Relaxed))?;
+        writeln!(out, "{} matched lines", self.matched_lines.load(Ordering::Relaxed))?;
+        writeln!(out, "{} bytes searched", self.bytes.load(Ordering::Relaxed))?;
+        writeln!(out, "{:.6} seconds", elapsed.as_secs_f64())
+    }
+}
+
+// Counts the bytes and lines of an input as the search consumes them, so a search that
+// stops early only counts what it read. A last line without a newline is counted when
+// the reader is dropped.
+pub struct CountingReader<'a, R> {
+    inner: R,
+    stats: &'a Stats,
+    // Whether the last byte consumed was anything but a newline
+    partial_line: bool,
+}
+
+impl<'a, R: BufRead> CountingReader<'a, R> {
+    pub fn new(inner: R, stats: &'a Stats) -> CountingReader<'a, R> {
+        CountingReader { inner, stats, partial_line: false }
+    }
+}
+
+impl<R: BufRead> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = {
+            let available = self.fill_buf()?;
+            let len = available.len().min(buf.len());
+            buf[..len].copy_from_slice(&available[..len]);
+            len
+        };
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<'_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if self.stats.enabled && amount > 0 {
+            if let Ok(buf) = self.inner.fill_buf() {
+                let consumed = &buf[..amount.min(buf.len())];
+                self.stats.add_read(consumed.len(), memchr::memchr_iter(b'\n', consumed).count());
+                self.partial_line = consumed.last() != Some(&b'\n');
+            }
+        }
+        self.inner.consume(amount);
+    }
+}
+
+impl<R> Drop for CountingReader<'_, R> {
+    fn drop(&mut self) {
+        if self.partial_line {
+            self.stats.add_read(0, 1);
+        }
+    }
+}
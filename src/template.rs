@@ -0,0 +1,106 @@
+// Output layouts given with --format, such as `{path}\t{line}\t{text}`
+
+// A value that can be filled into a template
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    Path,
+    Line,
+    Column,
+    Offset,
+    // The whole line
+    Text,
+    // The text of the first match on the line
+    Match,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        match name {
+            "path" => Some(Field::Path),
+            "line" => Some(Field::Line),
+            "column" => Some(Field::Column),
+            "offset" => Some(Field::Offset),
+            "text" => Some(Field::Text),
+            "match" => Some(Field::Match),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+// A parsed template. Placeholders name a field in braces, `{{` and `}}` stand for literal
+// braces, and `\t`, `\n`, `\0` and `\\` are unescaped so they can be typed in a shell.
+#[derive(Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    // Parse a template, returning None for an unknown placeholder or unbalanced brace
+    pub fn parse(template: &str) -> Option<Template> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next()? {
+                            '}' => break,
+                            c => name.push(c),
+                        }
+                    }
+                    let field = Field::from_name(&name)?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return None,
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('0') => literal.push('\0'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    }
+                    None => literal.push('\\'),
+                },
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Some(Template { parts })
+    }
+
+    // Fill in the template, looking up each field as it is reached
+    pub fn render(&self, value: impl Fn(Field) -> String) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Field(field) => output.push_str(&value(*field)),
+            }
+        }
+        output
+    }
+}
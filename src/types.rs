@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use glob::Pattern;
+
+// File types selectable with --type, each with the globs its file names match
+const BUILTIN_TYPES: [(&str, &[&str]); 20] = [
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"]),
+    ("csharp", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.jsx", "*.mjs", "*.cjs"]),
+    ("json", &["*.json"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+// The file types known by name, mapping each to its globs
+pub struct Types {
+    types: BTreeMap<String, Vec<String>>,
+}
+
+impl Types {
+    pub fn builtin() -> Types {
+        let types = BUILTIN_TYPES
+            .iter()
+            .map(|(name, globs)| (name.to_string(), globs.iter().map(|glob| glob.to_string()).collect()))
+            .collect();
+        Types { types }
+    }
+
+    // Add a definition such as `web:*.html,*.css,*.js`. Globs given for a type that
+    // already exists are added to the ones it has.
+    pub fn add(&mut self, definition: &str) -> Result<(), Box<dyn Error>> {
+        let invalid = || format!("invalid type definition: {}", definition);
+        let (name, globs) = definition.split_once(':').ok_or_else(invalid)?;
+        if name.is_empty() || globs.is_empty() {
+            return Err(invalid().into());
+        }
+        for glob in globs.split(',') {
+            // Check the glob now, so a bad definition is reported even if the type isn't used
+            Pattern::new(glob)?;
+        }
+        let entry = self.types.entry(name.to_string()).or_default();
+        entry.extend(globs.split(',').map(String::from));
+        Ok(())
+    }
+
+    // Each type's name and globs, in name order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.types.iter().map(|(name, globs)| (name.as_str(), globs.as_slice()))
+    }
+
+    // Compile the globs of the named types, failing on a name that isn't known
+    pub fn globs(&self, names: &[String]) -> Result<Vec<Pattern>, Box<dyn Error>> {
+        let mut patterns = Vec::new();
+        for name in names {
+            let globs = self.types.get(name).ok_or_else(|| format!("unknown file type: {}", name))?;
+            for glob in globs {
+                patterns.push(Pattern::new(glob)?);
+            }
+        }
+        Ok(patterns)
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::Path;
+use clap::error::{ContextKind, ErrorKind};
+use grep::{Command, Config, ReplaceConfig};
+
+fn parse(args: &[&str]) -> Config {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    Config::new(&args).unwrap()
+}
+
+#[test]
+fn combined_short_flags() {
+    let config = parse(&["grep", "-inr", "pattern", "dir"]);
+    assert!(config.is_case_insensitive);
+    assert!(config.print_line_no);
+    assert!(config.recursive_search);
+    assert_eq!(config.patterns, ["pattern"]);
+    assert_eq!(config.filenames, [Path::new("dir")]);
+}
+
+#[test]
+fn combined_flags_end_with_a_value() {
+    let config = parse(&["grep", "-icA2", "pattern"]);
+    assert!(config.is_case_insensitive);
+    assert!(config.count_only);
+    assert_eq!(config.after_context, 2);
+    assert!(config.filenames.is_empty());
+}
+
+#[test]
+fn combined_flags_of_replace() {
+    let args: Vec<String> = ["replace", "-iF", "a.b", "c", "file"].iter().map(|arg| arg.to_string()).collect();
+    let config = ReplaceConfig::new(&args).unwrap();
+    assert!(config.is_case_insensitive);
+    assert!(config.fixed_strings);
+    assert_eq!(config.filenames, [Path::new("file")]);
+}
+
+#[test]
+fn unknown_short_flag_suggests_other_case() {
+    let args: Vec<String> = ["grep", "-N", "pattern"].iter().map(|arg| arg.to_string()).collect();
+    let error = Config::new(&args).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::UnknownArgument);
+    assert_eq!(error.get(ContextKind::SuggestedArg).map(ToString::to_string), Some("-n".to_string()));
+}
+
+#[test]
+fn arguments_after_double_dash_are_positional() {
+    let config = parse(&["grep", "-i", "--", "-r", "-n"]);
+    assert!(!config.recursive_search);
+    assert!(!config.print_line_no);
+    assert_eq!(config.patterns, ["-r"]);
+    assert_eq!(config.filenames, [Path::new("-n")]);
+}
+
+#[test]
+fn option_values_can_start_with_a_dash() {
+    let config = parse(&["grep", "-e", "-r", "--group-separator", "--", "file"]);
+    assert_eq!(config.patterns, ["-r"]);
+    assert_eq!(config.group_separator.as_deref(), Some("--"));
+    assert_eq!(config.filenames, [Path::new("file")]);
+}
+
+#[test]
+fn search_is_the_default_subcommand() {
+    let args: Vec<String> = ["grep", "search", "-i", "pattern"].iter().map(|arg| arg.to_string()).collect();
+    let (command, rest) = Command::from_args(&args);
+    assert_eq!(command, Command::Search);
+    assert_eq!(rest, &args[1..]);
+    assert_eq!(Command::from_args(&args[1..]), (Command::Search, &args[1..]));
+}
+
+#[cfg(unix)]
+#[test]
+fn file_names_need_not_be_utf8() {
+    use std::ffi::{OsStr, OsString};
+    use std::os::unix::ffi::OsStrExt;
+
+    let file = OsStr::from_bytes(b"caf\xe9.txt");
+    let args = [OsString::from("grep"), OsString::from("pattern"), file.to_os_string()];
+    let config = Config::new(&args).unwrap();
+    assert_eq!(config.filenames, [Path::new(file)]);
+}
+
+#[test]
+fn multiline_rejects_what_it_does_not_support() {
+    for flag in ["-v", "-A1", "-B1", "-C1", "--passthru"] {
+        let args: Vec<String> = ["grep", "-U", flag, "pattern"].iter().map(|arg| arg.to_string()).collect();
+        let error = Config::new(&args).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict, "{}", flag);
+    }
+}
+
+#[test]
+fn output_modes_conflict() {
+    let modes = ["--vimgrep", "--json", "--json-lines", "--csv", "--format={path}"];
+    for (i, first) in modes.iter().enumerate() {
+        for second in &modes[i + 1..] {
+            let args: Vec<String> = ["grep", first, second, "pattern"].iter().map(|arg| arg.to_string()).collect();
+            let error = Config::new(&args).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::ArgumentConflict, "{} {}", first, second);
+        }
+    }
+}
+
+#[test]
+fn pattern_named_like_a_subcommand_needs_e_or_double_dash() {
+    for args in [["grep", "-e", "search", "notes.txt"], ["grep", "--", "search", "notes.txt"]] {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(Command::from_args(&args), (Command::Search, &args[..]));
+        let config = Config::new(&args).unwrap();
+        assert_eq!(config.patterns, ["search"]);
+        assert_eq!(config.filenames, [Path::new("notes.txt")]);
+    }
+
+    // Given first, the word names the subcommand and the next argument is the pattern
+    let args: Vec<String> = ["grep", "search", "notes.txt"].iter().map(|arg| arg.to_string()).collect();
+    let (command, rest) = Command::from_args(&args);
+    assert_eq!(command, Command::Search);
+    assert_eq!(Config::new(rest).unwrap().patterns, ["notes.txt"]);
+}
@@ -0,0 +1,86 @@
+use grep::{MatchOptions, Matcher, PatternMatcher};
+
+fn case_insensitive(patterns: &[&str], fixed_strings: bool) -> PatternMatcher {
+    let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+    let options = MatchOptions {
+        case_insensitive: true,
+        fixed_strings,
+        ..MatchOptions::default()
+    };
+    PatternMatcher::new(&patterns, &options).unwrap()
+}
+
+#[test]
+fn greek_sigma_matches_all_forms() {
+    let matcher = case_insensitive(&["σας"], false);
+    assert!(matcher.is_match("ΣΑΣ".as_bytes()));
+    // Final sigma folds to the same letter as Σ and σ
+    assert!(matcher.is_match("σας".as_bytes()));
+    assert!(matcher.is_match("ΣΑς".as_bytes()));
+}
+
+#[test]
+fn sharp_s_matches_capital_sharp_s() {
+    let matcher = case_insensitive(&["straße"], false);
+    assert!(matcher.is_match("STRAẞE".as_bytes()));
+    assert!(matcher.is_match("Straße".as_bytes()));
+}
+
+// Only simple case folding is done, so ß doesn't match the two letters it upper-cases to
+#[test]
+fn sharp_s_does_not_match_double_s() {
+    for fixed_strings in [false, true] {
+        let matcher = case_insensitive(&["straße"], fixed_strings);
+        assert!(!matcher.is_match("STRASSE".as_bytes()));
+    }
+}
+
+// İ has no simple case folding, so it only matches itself, and neither i nor I match it
+#[test]
+fn dotted_capital_i_matches_only_itself() {
+    for fixed_strings in [false, true] {
+        let dotted = case_insensitive(&["İ"], fixed_strings);
+        assert!(dotted.is_match("İ".as_bytes()));
+        assert!(!dotted.is_match("i".as_bytes()));
+        assert!(!dotted.is_match("I".as_bytes()));
+
+        let plain = case_insensitive(&["i"], fixed_strings);
+        assert!(!plain.is_match("İ".as_bytes()));
+    }
+}
+
+#[test]
+fn non_ascii_fixed_strings() {
+    let matcher = case_insensitive(&["привет.мир"], true);
+    assert!(matcher.is_match("ПРИВЕТ.МИР".as_bytes()));
+    // The `.` is literal with -F
+    assert!(!matcher.is_match("ПРИВЕТ МИР".as_bytes()));
+}
+
+#[test]
+fn kelvin_sign_folds_to_k() {
+    let matcher = case_insensitive(&["k"], false);
+    assert!(matcher.is_match("\u{212A}".as_bytes()));
+}
+
+#[test]
+fn match_offsets_are_byte_ranges() {
+    let matcher = case_insensitive(&["ÉTÉ"], false);
+    let line = "un été chaud";
+    assert_eq!(matcher.find_iter(line.as_bytes()), vec![(3, 8)]);
+    assert_eq!(&line[3..8], "été");
+}
+
+#[test]
+fn smart_case_sees_non_ascii_uppercase() {
+    let options = MatchOptions {
+        smart_case: true,
+        ..MatchOptions::default()
+    };
+    let lower = PatternMatcher::new(&["écran".to_string()], &options).unwrap();
+    assert!(lower.is_match("ÉCRAN".as_bytes()));
+
+    let upper = PatternMatcher::new(&["Écran".to_string()], &options).unwrap();
+    assert!(!upper.is_match("écran".as_bytes()));
+    assert!(upper.is_match("Écran".as_bytes()));
+}
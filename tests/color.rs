@@ -0,0 +1,48 @@
+use colored::Color;
+use grep::color::{ColorSpecs, Style};
+
+#[test]
+fn sgr_parameters_set_colors_and_styles() {
+    assert_eq!(Style::from_sgr("31"), Some(Style::fg(Color::Red)));
+    assert_eq!(Style::from_sgr("95"), Some(Style::fg(Color::BrightMagenta)));
+    assert_eq!(Style::from_sgr("01;31"), Style::from_sgr("1;31"));
+    assert_ne!(Style::from_sgr("01;31"), Some(Style::fg(Color::Red)));
+    // A reset and `22` undo what came before them
+    assert_eq!(Style::from_sgr("1;0;31"), Some(Style::fg(Color::Red)));
+    assert_eq!(Style::from_sgr("1;22;31"), Some(Style::fg(Color::Red)));
+    assert_eq!(Style::from_sgr(""), Some(Style::default()));
+}
+
+#[test]
+fn extended_colors_become_true_colors() {
+    let rgb = |r, g, b| Some(Style::fg(Color::TrueColor { r, g, b }));
+    assert_eq!(Style::from_sgr("38;5;1"), Some(Style::fg(Color::Red)));
+    // The 6x6x6 cube and the gray ramp of the 256-color palette
+    assert_eq!(Style::from_sgr("38;5;196"), rgb(255, 0, 0));
+    assert_eq!(Style::from_sgr("38;5;110"), rgb(135, 175, 215));
+    assert_eq!(Style::from_sgr("38;5;244"), rgb(128, 128, 128));
+    assert_eq!(Style::from_sgr("38;2;1;2;3"), rgb(1, 2, 3));
+}
+
+#[test]
+fn bad_sgr_parameters_are_rejected() {
+    for sgr in ["31;x", "5", "38;5", "38;2;1;2", "38;5;256", "38;9;1"] {
+        assert_eq!(Style::from_sgr(sgr), None, "{}", sgr);
+    }
+}
+
+#[test]
+fn grep_colors_sets_the_parts_it_knows() {
+    let mut specs = ColorSpecs::default();
+    specs.apply_grep_colors("ms=01;31:fn=35:ln=32:se=36:cx=1:bogus:ln=zz");
+    assert_eq!(Some(specs.matched), Style::from_sgr("1;31"));
+    assert_eq!(specs.path, Style::fg(Color::Magenta));
+    // An entry that can't be parsed leaves the earlier one in place
+    assert_eq!(specs.line, Style::fg(Color::Green));
+    assert_eq!(specs.separator, Style::fg(Color::Cyan));
+    assert_eq!(specs.column, Style::default());
+
+    let mut specs = ColorSpecs::default();
+    specs.apply_grep_colors("mt=32");
+    assert_eq!(specs.matched, Style::fg(Color::Green));
+}
@@ -0,0 +1,53 @@
+use std::fs;
+use std::process::Command;
+use grep::{search, Config, PatternMatcher};
+
+fn config(args: &[&str]) -> Config {
+    let args: Vec<String> = ["grep"].iter().chain(args).map(|arg| arg.to_string()).collect();
+    Config::new(&args).unwrap()
+}
+
+// The line numbers of the results, with `c` marking context lines
+fn lines(args: &[&str], text: &str) -> Vec<String> {
+    let config = config(args);
+    let matcher = PatternMatcher::new(&config.patterns, &config.match_options()).unwrap();
+    search(&config, &matcher, text)
+        .iter()
+        .map(|m| format!("{}{}", m.line_no, if m.is_context { "c" } else { "" }))
+        .collect()
+}
+
+const TEXT: &str = "1\n2\n3\nx4\n5\n6\n7\n8\nx9\n10\n";
+
+#[test]
+fn before_context_keeps_only_the_last_lines() {
+    assert_eq!(lines(&["-B2", "x"], TEXT), ["2c", "3c", "4", "7c", "8c", "9"]);
+}
+
+#[test]
+fn after_context_counts_down_after_each_match() {
+    assert_eq!(lines(&["-A2", "x"], TEXT), ["4", "5c", "6c", "9", "10c"]);
+}
+
+#[test]
+fn overlapping_context_is_printed_once() {
+    assert_eq!(lines(&["-C3", "x"], TEXT), ["1c", "2c", "3c", "4", "5c", "6c", "7c", "8c", "9", "10c"]);
+}
+
+#[test]
+fn separators_go_between_groups_that_are_not_adjacent() {
+    let path = std::env::temp_dir().join("grep-rust-context-test.txt");
+    fs::write(&path, TEXT).unwrap();
+    let grep = |context: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_grep"))
+            .args([context.as_ref(), "x".as_ref(), path.as_os_str()])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(grep("-A1"), "x4\n5\n--\nx9\n10\n");
+    assert_eq!(grep("-A2"), "x4\n5\n6\n--\nx9\n10\n");
+    // Lines 5 to 8 join the two groups, so there is nothing to separate
+    assert_eq!(grep("-A4"), "x4\n5\n6\n7\n8\nx9\n10\n");
+    fs::remove_file(&path).unwrap();
+}
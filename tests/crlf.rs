@@ -0,0 +1,23 @@
+use grep::{search, search_multiline, Config, PatternMatcher};
+
+fn config(args: &[&str]) -> Config {
+    Config::new(args).unwrap()
+}
+
+#[test]
+fn whole_line_match_ignores_crlf() {
+    let config = config(&["grep", "-x", "foo"]);
+    let matcher = PatternMatcher::new(&config.patterns, &config.match_options()).unwrap();
+    let matches = search(&config, &matcher, "foo bar\r\nfoo\r\n");
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].line, "foo");
+}
+
+#[test]
+fn multiline_end_of_line_before_crlf() {
+    let config = config(&["grep", "-U", "bar$"]);
+    let matcher = PatternMatcher::new(&config.patterns, &config.match_options()).unwrap();
+    let matches = search_multiline(&config, &matcher, "foo bar\r\nbaz\r\n");
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].line, "foo bar");
+}
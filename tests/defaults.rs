@@ -0,0 +1,14 @@
+use std::env;
+use std::ffi::OsString;
+use grep::defaults::with_defaults;
+use grep::Config;
+
+#[test]
+fn command_line_flags_undo_defaults() {
+    env::set_var("GREP_RUST_OPTS", "--smart-case --line-number --no-pager");
+    let args: Vec<OsString> = ["grep", "--no-config", "--no-smart-case", "--pager", "pattern"].iter().map(OsString::from).collect();
+    let config = Config::new(with_defaults(&args).unwrap()).unwrap();
+    env::remove_var("GREP_RUST_OPTS");
+    assert!(!config.smart_case);
+    assert!(config.print_line_no);
+}
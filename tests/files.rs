@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::PathBuf;
+use grep::files::FileErrors;
+use grep::{parse_filenames, Config, FileFilter};
+
+#[test]
+fn directory_without_recursion_is_a_file_error() {
+    let errors = FileErrors::new(true);
+    let files = parse_filenames(&[PathBuf::from("src")], false, &FileFilter::default(), &errors);
+    assert!(files.is_empty());
+    assert_eq!(errors.count(), 1);
+}
+
+// Create the files under a fresh directory, and list what `grep -r` with the options
+// would search there, relative to it
+fn walk(name: &str, files: &[(&str, &str)], options: &[&str]) -> Vec<String> {
+    let dir = std::env::temp_dir().join(format!("grep-rust-walk-{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    for (file, contents) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    let mut args = vec!["grep".to_string(), "-r".to_string()];
+    args.extend(options.iter().map(|option| option.to_string()));
+    args.extend(["pattern".to_string(), dir.to_string_lossy().into_owned()]);
+    let config = Config::new(&args).unwrap();
+    let errors = FileErrors::new(true);
+    let files = parse_filenames(&config.filenames, true, &FileFilter::new(&config).unwrap(), &errors);
+    let files = files.iter().map(|file| file.strip_prefix(&dir).unwrap().to_string_lossy().into_owned()).collect();
+    fs::remove_dir_all(&dir).unwrap();
+    files
+}
+
+#[test]
+fn gitignore_rules_skip_paths_until_a_nested_file_re_includes_them() {
+    let files = [
+        (".gitignore", "*.log\ntarget/\n"),
+        ("a.log", ""),
+        ("main.rs", ""),
+        ("target/out.rs", ""),
+        (".git/config", ""),
+        ("logs/.gitignore", "!keep.log\n"),
+        ("logs/keep.log", ""),
+        ("logs/drop.log", ""),
+    ];
+    assert_eq!(walk("gitignore", &files, &[]), ["logs/keep.log", "main.rs"]);
+    // --no-ignore searches everything, hidden files included
+    assert_eq!(walk("no-ignore", &files, &["--no-ignore"]).len(), files.len());
+}
+
+#[test]
+fn later_ignore_files_take_precedence() {
+    let files = [
+        (".gitignore", "*.txt\n"),
+        (".ignore", "!notes.txt\n*.tmp\n"),
+        (".grepignore", "notes.txt\n!keep.tmp\n"),
+        ("notes.txt", ""),
+        ("todo.txt", ""),
+        ("scratch.tmp", ""),
+        ("keep.tmp", ""),
+        ("main.rs", ""),
+    ];
+    assert_eq!(walk("precedence", &files, &[]), ["keep.tmp", "main.rs"]);
+}
@@ -0,0 +1,40 @@
+use grep::{search, Config, MatchOptions, Matcher, PatternMatcher};
+
+// Matches whole words that are all digits
+struct NumberMatcher;
+
+impl Matcher for NumberMatcher {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        let mut i = start;
+        while i < haystack.len() {
+            let end = i + haystack[i..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+            let word_start = i == 0 || !haystack[i - 1].is_ascii_alphanumeric();
+            let word_end = end == haystack.len() || !haystack[end].is_ascii_alphanumeric();
+            if end > i && word_start && word_end {
+                return Some((i, end));
+            }
+            i = end.max(i + 1);
+        }
+        None
+    }
+}
+
+#[test]
+fn custom_matcher_drives_the_search() {
+    let config = Config::new(["grep", "unused"]).unwrap();
+    let matches = search(&config, &NumberMatcher, "no numbers\nabc123\nport 8080 open\n");
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].line_no, 3);
+    assert_eq!(NumberMatcher.find_iter(b"1 and 22"), vec![(0, 1), (6, 8)]);
+    assert_eq!(NumberMatcher.replace_all("port 8080", "N"), "port N");
+}
+
+#[test]
+fn literals_expand_the_whole_match() {
+    let patterns = |patterns: &[&str]| -> Vec<String> { patterns.iter().map(|p| p.to_string()).collect() };
+    let fixed = MatchOptions { fixed_strings: true, ..MatchOptions::default() };
+    let one = PatternMatcher::new(&patterns(&["foo"]), &fixed).unwrap();
+    assert_eq!(one.replace_all("foo bar", "[$0]"), "[foo] bar");
+    let two = PatternMatcher::new(&patterns(&["foo", "bar"]), &MatchOptions::default()).unwrap();
+    assert_eq!(two.replace_all("foo bar", "[${0}]$$"), "[foo]$ [bar]$");
+}
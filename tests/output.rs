@@ -0,0 +1,85 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+// Run a search over `input` given on stdin and return what it printed
+fn grep(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_grep"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+}
+
+#[test]
+fn closed_stdout_ends_the_search_quietly() {
+    let path = std::env::temp_dir().join("grep-rust-broken-pipe.txt");
+    fs::write(&path, "match\n".repeat(200_000)).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_grep"))
+        .args(["match".as_ref(), path.as_os_str()])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Read one line and close the pipe, as `head -1` would
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(line, "match\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn replace_fills_in_capture_groups() {
+    let input = "user=alice id=7\nnothing here\n";
+    assert_eq!(grep(&["--replace", "$2:$1", r"(\w+)=(\w+)"], input), "alice:user 7:id\n");
+    assert_eq!(grep(&["--replace", "${key}", r"(?P<key>\w+)=\w+"], input), "user id\n");
+    // With -o only the rewritten matches are printed
+    assert_eq!(grep(&["-o", "--replace", "<$1>", r"id=(\d+)"], input), "<7>\n");
+}
+
+#[test]
+fn csv_quotes_fields_that_need_it() {
+    let input = "a,b \"c\"\nplain a\ncarriage\ra\n";
+    let expected = "path,line,column,text\n\
+                    (standard input),1,1,\"a,b \"\"c\"\"\"\n\
+                    (standard input),2,3,plain a\n\
+                    (standard input),3,2,\"carriage\ra\"\n";
+    assert_eq!(grep(&["--csv", "a"], input), expected);
+
+    let path = std::env::temp_dir().join("grep-rust-csv,test.txt");
+    fs::write(&path, "a\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_grep"))
+        .args(["--csv".as_ref(), "a".as_ref(), path.as_os_str()])
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    let row = format!("\"{}\",1,1,a\n", path.display());
+    assert!(String::from_utf8(output.stdout).unwrap().ends_with(&row));
+}
+
+#[test]
+fn max_columns_cuts_long_lines_and_counts_the_bytes_left_out() {
+    let line = "abcdefghijklmnopqrstuvwxyz\n";
+    assert_eq!(grep(&["-M10", "a"], line), "abcdefghij… [16 bytes omitted]\n");
+    assert_eq!(grep(&["-M25", "a"], line), "abcdefghijklmnopqrstuvwxy… [1 byte omitted]\n");
+    assert_eq!(grep(&["-M26", "a"], line), line);
+    // Columns are characters, while what is left out is counted in bytes
+    assert_eq!(grep(&["-M3", "é"], "éééé\n"), "ééé… [2 bytes omitted]\n");
+}
+
+#[test]
+fn max_columns_centers_the_first_match() {
+    let line = "abcdefghijklmnopqrstuvwxyz\n";
+    assert_eq!(grep(&["-M10", "m"], line), "…ijklmnopqr… [16 bytes omitted]\n");
+    assert_eq!(grep(&["-M10", "mno"], line), "…jklmnopqrs… [16 bytes omitted]\n");
+    // Near either end the window stops at the end of the line
+    assert_eq!(grep(&["-M10", "c"], line), "abcdefghij… [16 bytes omitted]\n");
+    assert_eq!(grep(&["-M10", "y"], line), "…qrstuvwxyz [16 bytes omitted]\n");
+    assert_eq!(grep(&["-M5", "X"], "ééééééééééXéééééééééé\n"), "…ééXéé… [32 bytes omitted]\n");
+}
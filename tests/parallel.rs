@@ -0,0 +1,66 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn grep(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_grep")).args(args).current_dir(dir).output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn threads_print_in_the_sequential_order() {
+    let dir = std::env::temp_dir().join("grep-rust-parallel-order");
+    let _ = fs::remove_dir_all(&dir);
+    for sub in ["a", "b/c", "b/d", "m"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+        for file in ["1", "2", "x", "zz"] {
+            fs::write(dir.join(sub).join(file), "hit\n").unwrap();
+        }
+    }
+    fs::write(dir.join("zz"), "hit\n").unwrap();
+    fs::write(dir.join("0"), "hit\n").unwrap();
+
+    let sequential = grep(&dir, &["-rf", "-j1", "hit", "."]);
+    assert!(sequential.starts_with("./0: ") && sequential.ends_with("./zz: hit\n"), "{}", sequential);
+    for _ in 0..5 {
+        assert_eq!(grep(&dir, &["-rf", "-j4", "hit", "."]), sequential);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_loops_are_skipped_whatever_the_threads() {
+    let dir = std::env::temp_dir().join("grep-rust-parallel-loop");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/file"), "hit\n").unwrap();
+    std::os::unix::fs::symlink("..", dir.join("sub/loop")).unwrap();
+
+    for threads in ["-j1", "-j4"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_grep"))
+            .args(["-rf", "--follow", threads, "hit", "."])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0), "{}", threads);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "./sub/file: hit\n");
+        assert!(output.stderr.is_empty(), "{}", threads);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn recursive_search_without_files_reads_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_grep"))
+        .args(["-r", "-j4", "foo"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"foo\nbar\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "foo\n");
+}
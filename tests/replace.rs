@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use grep::{run_replace, ReplaceConfig};
+
+// A fresh directory holding `a.txt` with the given contents
+fn setup(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("grep-rust-replace-{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("a.txt"), contents).unwrap();
+    dir
+}
+
+fn replace(args: &[&str], dir: &Path) -> bool {
+    let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    args.insert(0, "replace".to_string());
+    args.push(dir.to_string_lossy().into_owned());
+    run_replace(ReplaceConfig::new(&args).unwrap()).unwrap()
+}
+
+fn read(dir: &Path, name: &str) -> String {
+    fs::read_to_string(dir.join(name)).unwrap()
+}
+
+#[test]
+fn backups_are_kept_across_runs() {
+    let dir = setup("backup", b"foo one\n");
+    assert!(replace(&["-r", "foo", "bar"], &dir));
+    assert!(replace(&["-r", "one", "two"], &dir));
+    assert_eq!(read(&dir, "a.txt"), "bar two\n");
+    assert_eq!(read(&dir, "a.txt.bak"), "foo one\n");
+    assert_eq!(read(&dir, "a.txt.1.bak"), "bar one\n");
+    assert!(!dir.join("a.txt.bak.bak").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn no_backup_leaves_only_the_rewritten_file() {
+    let dir = setup("no-backup", b"foo\n");
+    assert!(replace(&["-r", "--no-backup", "foo", "bar"], &dir));
+    assert_eq!(read(&dir, "a.txt"), "bar\n");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dry_run_writes_nothing() {
+    let dir = setup("dry-run", b"foo\n");
+    assert!(replace(&["-r", "--dry-run", "foo", "bar"], &dir));
+    assert_eq!(read(&dir, "a.txt"), "foo\n");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn binary_files_are_not_modified() {
+    let dir = setup("binary", b"foo\0\n");
+    assert!(!replace(&["-r", "foo", "bar"], &dir));
+    assert_eq!(fs::read(dir.join("a.txt")).unwrap(), b"foo\0\n");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_followed() {
+    let dir = setup("symlink", b"foo\n");
+    std::os::unix::fs::symlink("a.txt", dir.join("link.txt")).unwrap();
+    assert!(replace(&["--no-backup", "foo", "bar"], &dir.join("link.txt")));
+    assert_eq!(read(&dir, "a.txt"), "bar\n");
+    assert!(fs::symlink_metadata(dir.join("link.txt")).unwrap().file_type().is_symlink());
+    fs::remove_dir_all(&dir).unwrap();
+}
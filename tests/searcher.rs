@@ -0,0 +1,51 @@
+use std::fs;
+use std::ops::Range;
+use grep::searcher::search_file;
+use grep::{FileMatch, PatternMatcher, Searcher};
+
+fn matcher(pattern: &str) -> PatternMatcher {
+    PatternMatcher::new(&[pattern.to_string()], &Default::default()).unwrap()
+}
+
+#[test]
+fn builder_sets_context() {
+    let searcher = Searcher::builder().context(1, 1).build().unwrap();
+    let matches = searcher.search_str(&matcher("c"), "a\nb\nc\nd\ne\n");
+    let lines: Vec<(usize, &str, bool)> = matches.iter().map(|m| (m.line_no, m.line.as_str(), m.is_context)).collect();
+    assert_eq!(lines, [(2, "b", true), (3, "c", false), (4, "d", true)]);
+}
+
+#[test]
+fn builder_rejects_inverted_multiline() {
+    assert!(Searcher::builder().multiline(true).invert(true).build().is_err());
+}
+
+#[test]
+fn search_path_prints_like_the_command_line() {
+    let path = std::env::temp_dir().join("grep-rust-searcher-test.txt");
+    fs::write(&path, "one\ntwo\nthree\n").unwrap();
+    let searcher = Searcher::builder().line_numbers(true).build().unwrap();
+    let mut out = Vec::new();
+    assert!(searcher.search_path(&matcher("t"), &path, &mut out).unwrap());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "2: two\n3: three\n");
+}
+
+#[test]
+fn search_file_yields_matches_with_their_spans() {
+    let path = std::env::temp_dir().join("grep-rust-search-file-test.txt");
+    fs::write(&path, "one\ntwo\nthree\n").unwrap();
+    let matches: Vec<FileMatch> = search_file(&path, &matcher("t")).unwrap().map(Result::unwrap).collect();
+    fs::remove_file(&path).unwrap();
+    let found: Vec<(usize, Range<usize>, &str)> = matches.iter().map(|m| (m.line_no, m.span.clone(), m.line.as_str())).collect();
+    assert_eq!(found, [(2, 4..5, "two"), (3, 8..9, "three")]);
+    assert!(matches.iter().all(|m| m.path == path));
+}
+
+#[test]
+fn inverted_max_count_stops_selecting() {
+    let searcher = Searcher::builder().invert(true).max_count(Some(1)).context(0, 1).build().unwrap();
+    let matches = searcher.search_str(&matcher("zzz"), "a\nb\nc\nd\ne\nf\n");
+    let lines: Vec<(usize, &str, bool)> = matches.iter().map(|m| (m.line_no, m.line.as_str(), m.is_context)).collect();
+    assert_eq!(lines, [(1, "a", false), (2, "b", true)]);
+}
@@ -0,0 +1,35 @@
+use grep::template::{Field, Template};
+
+// Render a template with each field filled in by its name
+fn render(template: &str) -> Option<String> {
+    let template = Template::parse(template)?;
+    Some(template.render(|field| {
+        match field {
+            Field::Path => "PATH",
+            Field::Line => "LINE",
+            Field::Column => "COLUMN",
+            Field::Offset => "OFFSET",
+            Field::Text => "TEXT",
+            Field::Match => "MATCH",
+        }
+        .to_string()
+    }))
+}
+
+#[test]
+fn placeholders_are_filled_in() {
+    assert_eq!(render("{path}:{line}:{column}:{offset} {match} in {text}").unwrap(), "PATH:LINE:COLUMN:OFFSET MATCH in TEXT");
+}
+
+#[test]
+fn escapes_and_doubled_braces_are_literal() {
+    assert_eq!(render(r"{path}\t{{line}}\n\0\\\x").unwrap(), "PATH\t{line}\n\0\\\\x");
+    assert_eq!(render(r"trailing \").unwrap(), "trailing \\");
+}
+
+#[test]
+fn bad_placeholders_are_rejected() {
+    assert!(render("{file}").is_none());
+    assert!(render("{path").is_none());
+    assert!(render("path}").is_none());
+}